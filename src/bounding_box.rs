@@ -0,0 +1,102 @@
+use vector::Vector;
+use ray::Ray;
+use std::f64;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingBox {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl BoundingBox {
+    pub fn new(a: Vector, b: Vector) -> BoundingBox {
+        BoundingBox {
+            min: Vector::min(&a, &b),
+            max: Vector::max(&a, &b),
+        }
+    }
+
+    pub fn empty() -> BoundingBox {
+        BoundingBox {
+            min: Vector::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<I: IntoIterator<Item=Vector>>(points: I) -> BoundingBox {
+        points.into_iter().fold(BoundingBox::empty(), |bounding_box, point| bounding_box.including(&point))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: Vector::min(&self.min, &other.min),
+            max: Vector::max(&self.max, &other.max),
+        }
+    }
+
+    /// The part within both boxes, which may be empty
+    pub fn intersection(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: Vector::max(&self.min, &other.min),
+            max: Vector::min(&self.max, &other.max),
+        }
+    }
+
+    pub fn including(&self, point: &Vector) -> BoundingBox {
+        BoundingBox {
+            min: Vector::min(&self.min, point),
+            max: Vector::max(&self.max, point),
+        }
+    }
+
+    pub fn centroid(&self) -> Vector {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vector {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let e = self.extent();
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    pub fn largest_axis(&self) -> usize {
+        let e = self.extent();
+        if e.x >= e.y && e.x >= e.z {
+            0
+        } else if e.y >= e.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test. Returns the distance at which the ray enters the box (0 if it starts inside),
+    /// or None if the box is missed or only reached beyond `max_distance`.
+    /// `inverse_direction` is `ray.direction.recip()`, passed in so it is computed once per ray.
+    pub fn intersect(&self, ray: &Ray, inverse_direction: &Vector, max_distance: f64) -> Option<f64> {
+        let mut t_near = 0.0f64;
+        let mut t_far = max_distance;
+        for axis in 0..3 {
+            let t1 = (self.min[axis] - ray.position[axis]) * inverse_direction[axis];
+            let t2 = (self.max[axis] - ray.position[axis]) * inverse_direction[axis];
+            // Note: f64::min/max ignore NaNs (ray origin on a slab boundary with a parallel ray)
+            t_near = t_near.max(t1.min(t2));
+            t_far = t_far.min(t1.max(t2));
+        }
+        if t_near <= t_far {
+            Some(t_near)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use model_object::ModelObject;
+use bounding_box::BoundingBox;
+use vector::Vector;
+use ray::Ray;
+use hit::Hit;
+use std::f64;
+
+/// Bounding volume hierarchy over the scene objects, built with the surface area heuristic.
+/// Objects without a bounding box (e.g. infinite planes) are kept aside and tested on every query,
+/// and so are all objects of scenes too small for the hierarchy to pay off.
+pub struct Bvh {
+    objects: Vec<Box<dyn ModelObject + Send + Sync>>,
+    linear_objects: Vec<Box<dyn ModelObject + Send + Sync>>,
+    nodes: Vec<BvhNode>,
+}
+
+struct BvhNode {
+    bounding_box: BoundingBox,
+    kind: BvhNodeKind,
+}
+
+enum BvhNodeKind {
+    /// Covers `objects[first..first + count]`
+    Leaf { first: usize, count: usize },
+    /// The first child immediately follows its parent in `nodes`
+    Interior { second_child: usize, axis: usize },
+}
+
+struct BuildItem {
+    object_index: usize,
+    bounding_box: BoundingBox,
+    centroid: Vector,
+}
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+/// Deeper nodes are leaves whatever their size, so that traversal fits a fixed size stack
+const MAX_DEPTH: usize = 48;
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+/// Below this many bounded objects, testing them all is faster than traversing a hierarchy
+const MIN_BVH_OBJECTS: usize = 8;
+
+impl Bvh {
+    pub fn new(objects: Vec<Box<dyn ModelObject + Send + Sync>>) -> Bvh {
+        let mut items: Vec<BuildItem> = Vec::new();
+        let mut bounded_objects: Vec<Option<Box<dyn ModelObject + Send + Sync>>> = Vec::new();
+        let mut linear_objects = Vec::new();
+        let bounded_count = objects.iter().filter(|object| object.bounding_box().is_some()).count();
+        for object in objects {
+            match object.bounding_box() {
+                Some(bounding_box) if bounded_count >= MIN_BVH_OBJECTS => {
+                    items.push(BuildItem {
+                        object_index: bounded_objects.len(),
+                        bounding_box,
+                        centroid: bounding_box.centroid(),
+                    });
+                    bounded_objects.push(Some(object));
+                }
+                _ => linear_objects.push(object),
+            }
+        }
+
+        let mut nodes = Vec::new();
+        if !items.is_empty() {
+            build_node(&mut items, 0, 0, &mut nodes);
+        }
+
+        // Store the objects in leaf order, so every leaf covers a contiguous range
+        let objects = items.iter()
+            .map(|item| bounded_objects[item.object_index].take().unwrap())
+            .collect();
+
+        Bvh {
+            objects,
+            linear_objects,
+            nodes,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len() + self.linear_objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&(dyn ModelObject + Send + Sync)> {
+        self.objects.iter().chain(self.linear_objects.iter()).map(|object| object.as_ref())
+    }
+
+    pub fn find_closest_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let mut closest_hit: Option<Hit> = None;
+        self.visit_objects(ray, f64::INFINITY, |object, max_distance| {
+            match object.try_hit(ray) {
+                Some(hit) if hit.distance < max_distance => {
+                    let distance = hit.distance;
+                    closest_hit = Some(hit);
+                    distance
+                }
+                _ => max_distance,
+            }
+        });
+        closest_hit
+    }
+
+    /// Returns all hits up to `max_distance`, sorted by distance
+    pub fn find_hits_within(&self, ray: &Ray, max_distance: f64) -> Vec<Hit<'_>> {
+        let mut hits: Vec<Hit> = Vec::new();
+        self.visit_objects(ray, max_distance, |object, max_distance| {
+            if let Some(hit) = object.try_hit(ray) {
+                if hit.distance <= max_distance {
+                    hits.push(hit);
+                }
+            }
+            max_distance
+        });
+        //TODO: handle NANs somehow?
+        hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+        hits
+    }
+
+    /// Calls `visit` for every object whose bounding box the ray enters before `max_distance`.
+    /// `visit` returns the (possibly reduced) max distance for the rest of the traversal.
+    fn visit_objects<'a, F>(&'a self, ray: &Ray, mut max_distance: f64, mut visit: F)
+        where F: FnMut(&'a (dyn ModelObject + Send + Sync), f64) -> f64
+    {
+        for object in self.linear_objects.iter() {
+            max_distance = visit(object.as_ref(), max_distance);
+        }
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let inverse_direction = ray.direction.recip();
+        // Holds at most one node per level, besides the one being visited
+        let mut stack = [0; MAX_DEPTH + 1];
+        let mut stack_size = 1;
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node_index = stack[stack_size];
+            let node = &self.nodes[node_index];
+            if node.bounding_box.intersect(ray, &inverse_direction, max_distance).is_none() {
+                continue;
+            }
+            match node.kind {
+                BvhNodeKind::Leaf { first, count } => {
+                    for object in self.objects[first..first + count].iter() {
+                        max_distance = visit(object.as_ref(), max_distance);
+                    }
+                }
+                BvhNodeKind::Interior { second_child, axis } => {
+                    // Visit the child nearer to the ray origin first, so max_distance shrinks sooner
+                    let (near, far) = if ray.direction[axis] < 0.0 {
+                        (second_child, node_index + 1)
+                    } else {
+                        (node_index + 1, second_child)
+                    };
+                    stack[stack_size] = far;
+                    stack[stack_size + 1] = near;
+                    stack_size += 2;
+                }
+            }
+        }
+    }
+}
+
+/// Recursively builds the subtree for `items`, which start at `first` in the final object order,
+/// at `depth` below the root. Returns the index of the subtree's root node.
+fn build_node(items: &mut [BuildItem], first: usize, depth: usize, nodes: &mut Vec<BvhNode>) -> usize {
+    let bounding_box = items.iter().fold(BoundingBox::empty(), |b, item| b.union(&item.bounding_box));
+    let node_index = nodes.len();
+    nodes.push(BvhNode {
+        bounding_box,
+        kind: BvhNodeKind::Leaf { first, count: items.len() },
+    });
+    if items.len() == 1 || depth == MAX_DEPTH {
+        return node_index;
+    }
+
+    let centroid_box = BoundingBox::from_points(items.iter().map(|item| item.centroid));
+    let axis = centroid_box.largest_axis();
+    let axis_min = centroid_box.min[axis];
+    let axis_extent = centroid_box.extent()[axis];
+    if axis_extent <= 0.0 {
+        // All centroids coincide, no split can separate them
+        return node_index;
+    }
+    let bin_of = |item: &BuildItem| {
+        let bin = ((item.centroid[axis] - axis_min) / axis_extent * BIN_COUNT as f64) as usize;
+        bin.min(BIN_COUNT - 1)
+    };
+
+    let mut bin_counts = [0usize; BIN_COUNT];
+    let mut bin_boxes = [BoundingBox::empty(); BIN_COUNT];
+    for item in items.iter() {
+        let bin = bin_of(item);
+        bin_counts[bin] += 1;
+        bin_boxes[bin] = bin_boxes[bin].union(&item.bounding_box);
+    }
+
+    // Evaluate the SAH cost of splitting after each bin
+    let parent_area = bounding_box.surface_area();
+    let mut best_split = 0;
+    let mut best_cost = f64::INFINITY;
+    for split in 0..BIN_COUNT - 1 {
+        let (left_box, left_count) = bin_boxes[..split + 1].iter().zip(bin_counts[..split + 1].iter())
+            .fold((BoundingBox::empty(), 0), |(b, n), (bin_box, count)| (b.union(bin_box), n + count));
+        let (right_box, right_count) = bin_boxes[split + 1..].iter().zip(bin_counts[split + 1..].iter())
+            .fold((BoundingBox::empty(), 0), |(b, n), (bin_box, count)| (b.union(bin_box), n + count));
+        if left_count == 0 || right_count == 0 {
+            continue;
+        }
+        let cost = TRAVERSAL_COST + INTERSECTION_COST
+            * (left_box.surface_area() * left_count as f64 + right_box.surface_area() * right_count as f64)
+            / parent_area;
+        if cost < best_cost {
+            best_cost = cost;
+            best_split = split;
+        }
+    }
+
+    let leaf_cost = INTERSECTION_COST * items.len() as f64;
+    if items.len() <= MAX_LEAF_SIZE && best_cost >= leaf_cost {
+        return node_index;
+    }
+
+    let mut middle = 0;
+    for i in 0..items.len() {
+        if bin_of(&items[i]) <= best_split {
+            items.swap(i, middle);
+            middle += 1;
+        }
+    }
+    if middle == 0 || middle == items.len() {
+        // Degenerate binning (e.g. a zero area parent box), fall back to an equal counts split
+        items.sort_by(|a, b| a.centroid[axis].partial_cmp(&b.centroid[axis]).unwrap());
+        middle = items.len() / 2;
+    }
+
+    let (left_items, right_items) = items.split_at_mut(middle);
+    build_node(left_items, first, depth + 1, nodes);
+    let second_child = build_node(right_items, first + middle, depth + 1, nodes);
+    nodes[node_index].kind = BvhNodeKind::Interior { second_child, axis };
+    node_index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sphere::Sphere;
+    use triangle::Triangle;
+    use plane::Plane;
+    use material::Material;
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    fn random_vector<R: Rng>(rng: &mut R, scale: f64) -> Vector {
+        Vector::new(rng.next_f64() - 0.5, rng.next_f64() - 0.5, rng.next_f64() - 0.5) * scale
+    }
+
+    #[test]
+    fn test_matches_linear_scan() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut objects: Vec<Box<dyn ModelObject + Send + Sync>> = Vec::new();
+        for _ in 0..200 {
+            let center = random_vector(&mut rng, 20.0);
+            objects.push(Box::new(Sphere { material: Material::default(), center, radius: rng.next_f64() }));
+            objects.push(Box::new(Triangle::new(
+                center + random_vector(&mut rng, 2.0),
+                center + random_vector(&mut rng, 2.0),
+                center + random_vector(&mut rng, 2.0),
+                Material::default())));
+        }
+        objects.push(Box::new(Plane { material: Material::default(), normal: Vector::new(0.0, 1.0, 0.0), offset: -15.0 }));
+        let bvh = Bvh::new(objects);
+        assert_eq!(bvh.len(), 401);
+
+        for _ in 0..1000 {
+            let position = random_vector(&mut rng, 30.0);
+            let ray = Ray::new(position, random_vector(&mut rng, 1.0).normalized());
+            let mut linear_hits: Vec<Hit> = bvh.iter().filter_map(|object| object.try_hit(&ray)).collect();
+            linear_hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+
+            let closest_distance = bvh.find_closest_hit(&ray).map(|hit| hit.distance);
+            assert_eq!(closest_distance, linear_hits.first().map(|hit| hit.distance));
+
+            let max_distance = 10.0;
+            let hits_within = bvh.find_hits_within(&ray, max_distance);
+            let linear_hits_within: Vec<f64> = linear_hits.iter()
+                .map(|hit| hit.distance)
+                .take_while(|&distance| distance <= max_distance)
+                .collect();
+            assert_eq!(hits_within.iter().map(|hit| hit.distance).collect::<Vec<f64>>(), linear_hits_within);
+        }
+    }
+
+    #[test]
+    fn test_small_scenes_are_scanned_linearly() {
+        let objects: Vec<Box<dyn ModelObject + Send + Sync>> = (0..3)
+            .map(|i| Box::new(Sphere { material: Material::default(), center: Vector::new(0.0, 0.0, 2.0 * i as f64 + 5.0), radius: 0.5 }) as Box<dyn ModelObject + Send + Sync>)
+            .collect();
+        let bvh = Bvh::new(objects);
+        assert!(bvh.nodes.is_empty());
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(bvh.find_closest_hit(&ray).map(|hit| hit.distance), Some(4.5));
+        assert_eq!(bvh.find_hits_within(&ray, 7.0).len(), 2);
+    }
+}
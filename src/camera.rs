@@ -1,6 +1,127 @@
 use vector::Vector;
 use ray::Ray;
-use rand;
+use sampler::Sampler;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+/// How much of the scene the camera sees
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FieldOfView {
+    /// A screen of the given width, at the given distance from the camera position
+    Screen { distance: f64, width: f64 },
+    /// Horizontal angle, in degrees
+    Horizontal(f64),
+    /// Vertical angle, in degrees
+    Vertical(f64),
+    /// Focal length in millimeters of a 35mm camera (36x24mm frame) with the same diagonal angle
+    FocalLength35mm(f64),
+}
+
+/// sqrt(36^2 + 24^2)
+const FRAME_35MM_DIAGONAL: f64 = 43.266_615_305_567_87;
+
+impl FieldOfView {
+    /// Screen distance and width for an image with the given aspect ratio (width / height)
+    fn screen_size(&self, aspect_ratio: f64) -> (f64, f64) {
+        match *self {
+            FieldOfView::Screen { distance, width } => (distance, width),
+            FieldOfView::Horizontal(degrees) => (1.0, 2.0 * (degrees.to_radians() / 2.0).tan()),
+            FieldOfView::Vertical(degrees) => (1.0, 2.0 * (degrees.to_radians() / 2.0).tan() * aspect_ratio),
+            FieldOfView::FocalLength35mm(focal_length) => {
+                let diagonal = FRAME_35MM_DIAGONAL / focal_length;
+                (1.0, diagonal * aspect_ratio / (aspect_ratio * aspect_ratio + 1.0).sqrt())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    /// Rays start at the camera position and pass through the screen
+    Perspective,
+    /// Rays start on the screen and are parallel to the view direction,
+    /// so the screen size is the size of the visible area
+    Orthographic,
+    /// 360 degree panorama, with longitude along the image width and latitude along its height
+    Equirectangular,
+    /// Six faces of a cube around the camera, side by side in the order right, left, up, down,
+    /// front and back. The faces are only square if the view is six times as wide as it is high.
+    CubeMap,
+    /// Circular fisheye image, inscribed in the image, covering `field_of_view` degrees
+    Fisheye { mapping: FisheyeMapping, field_of_view: f64 },
+}
+
+/// How a fisheye lens maps the angle from the view direction to the distance from the image center
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FisheyeMapping {
+    /// r = f * theta
+    Equidistant,
+    /// r = 2f * sin(theta / 2), which keeps relative areas
+    Equisolid,
+}
+
+/// Brown-Conrady lens distortion, with the coefficients in the order used by OpenCV.
+/// Coordinates are on a screen at distance 1, with y pointing down the image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LensDistortion {
+    pub k1: f64,
+    pub k2: f64,
+    pub p1: f64,
+    pub p2: f64,
+    pub k3: f64,
+}
+
+impl LensDistortion {
+    /// Where an undistorted point ends up on the image
+    pub fn distort(&self, x: f64, y: f64) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+        let (tangential_x, tangential_y) = self.tangential(x, y, r2);
+        (x * radial + tangential_x, y * radial + tangential_y)
+    }
+
+    /// Inverse of `distort`, found by fixed point iteration
+    pub fn undistort(&self, distorted_x: f64, distorted_y: f64) -> (f64, f64) {
+        let (mut x, mut y) = (distorted_x, distorted_y);
+        for _ in 0..20 {
+            let r2 = x * x + y * y;
+            let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+            let (tangential_x, tangential_y) = self.tangential(x, y, r2);
+            x = (distorted_x - tangential_x) / radial;
+            y = (distorted_y - tangential_y) / radial;
+        }
+        (x, y)
+    }
+
+    fn tangential(&self, x: f64, y: f64, r2: f64) -> (f64, f64) {
+        (2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x),
+         self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StereoLayout {
+    /// Left eye on the left half of the image
+    SideBySide,
+    /// Left eye on the top half of the image
+    TopBottom,
+}
+
+/// Renders a view for each eye into one image. Screen based projections use off-axis frustums,
+/// so objects at the convergence distance appear at the same place for both eyes.
+/// Other projections only move the eyes apart.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stereo {
+    pub interocular_distance: f64,
+    pub convergence_distance: f64,
+    pub layout: StereoLayout,
+}
+
+/// Sampler dimensions used by the camera
+/// Largest super sampling N whose N x N samples per pixel still fit in a u32
+pub const MAX_SUPER_SAMPLING_N: u32 = 65535;
+
+pub const FILM_DIMENSION: u32 = 0;
+pub const LENS_DIMENSION: u32 = 1;
 
 pub struct Camera {
     pub position: Vector,
@@ -14,104 +135,472 @@ pub struct Camera {
     pub image_width: u32,
     pub screen_center: Vector,
     pub super_sampling_n: u32,
-    //    pub aperture_size : f64,
-    //    pub dof_rays : u32,
+    pub field_of_view: FieldOfView,
+    pub projection: Projection,
+    /// Distortion of the screen, for the perspective and orthographic projections
+    pub distortion: Option<LensDistortion>,
+    /// Radius of the thin lens. Zero makes a pinhole camera, with everything in focus.
+    pub aperture_radius: f64,
+    /// Distance (along the view direction) of the plane that is in perfect focus
+    pub focal_distance: f64,
 
-    sub_pixel_width: f64,
-    sub_pixel_height: f64,
+    stereo: Option<Stereo>,
+    /// Size of the part of the image seen by one eye, which is the whole image without stereo
+    view_width: u32,
+    view_height: u32,
+    pixel_width: f64,
+    pixel_height: f64,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         position: Vector,
         look_at: Vector,
         up: Vector,
         screen_distance: f64,
         screen_width: f64,
+        image_width: u32,
         image_height: u32,
+        super_sampling_n: u32,
+    ) -> Camera {
+        let field_of_view = FieldOfView::Screen { distance: screen_distance, width: screen_width };
+        Camera::with_field_of_view(position, look_at, up, field_of_view, image_width, image_height, super_sampling_n)
+    }
+
+    pub fn with_field_of_view(
+        position: Vector,
+        look_at: Vector,
+        up: Vector,
+        field_of_view: FieldOfView,
         image_width: u32,
+        image_height: u32,
         super_sampling_n: u32,
     ) -> Camera {
         let direction = position.direction_to(&look_at);
         let right = *(up ^ direction).normalize();
         let up_corrected = *(right ^ direction).normalize();
-        let screen_height = screen_width * (image_height as f64 / image_width as f64);
-        let pixel_width = screen_width / image_width as f64;
-        let pixel_height = screen_height / image_height as f64;
-        let sub_pixel_width = pixel_width / super_sampling_n as f64;
-        let sub_pixel_height = pixel_height / super_sampling_n as f64;
-        Camera {
+        let mut camera = Camera {
             position,
             direction,
             up: up_corrected,
             right,
-            screen_distance,
-            screen_height,
-            screen_width,
-            image_height,
-            image_width,
-            screen_center: position + direction * screen_distance,
+            screen_distance: 0.0,
+            screen_height: 0.0,
+            screen_width: 0.0,
+            image_height: 0,
+            image_width: 0,
+            screen_center: position,
             super_sampling_n,
-            sub_pixel_width,
-            sub_pixel_height,
+            field_of_view,
+            projection: Projection::Perspective,
+            distortion: None,
+            aperture_radius: 0.0,
+            focal_distance: 0.0,
+            stereo: None,
+            view_width: 0,
+            view_height: 0,
+            pixel_width: 0.0,
+            pixel_height: 0.0,
+        };
+        camera.set_resolution(image_width, image_height);
+        camera.focal_distance = camera.screen_distance;
+        camera
+    }
+
+    /// Changes the output image size. The screen is resized to keep the camera's field of view,
+    /// the other dimension follows the aspect ratio.
+    pub fn set_resolution(&mut self, image_width: u32, image_height: u32) {
+        self.image_width = image_width;
+        self.image_height = image_height;
+        self.view_width = image_width;
+        self.view_height = image_height;
+        match self.stereo.map(|stereo| stereo.layout) {
+            Some(StereoLayout::SideBySide) => self.view_width = (image_width / 2).max(1),
+            Some(StereoLayout::TopBottom) => self.view_height = (image_height / 2).max(1),
+            None => {}
         }
+        let aspect_ratio = self.view_width as f64 / self.view_height as f64;
+        let (screen_distance, screen_width) = self.field_of_view.screen_size(aspect_ratio);
+        self.screen_distance = screen_distance;
+        self.screen_width = screen_width;
+        self.screen_center = self.position + self.direction * screen_distance;
+        self.screen_height = self.screen_width / aspect_ratio;
+        self.pixel_width = self.screen_width / self.view_width as f64;
+        self.pixel_height = self.screen_height / self.view_height as f64;
+    }
+
+    /// Whether the view is six times as wide as it is high, for the square faces of a cube map
+    pub fn has_cube_map_aspect(&self) -> bool {
+        u64::from(self.view_width) == 6 * u64::from(self.view_height)
+    }
+
+    /// Sets the resolution that gives each view six square cube map faces of `face_size` pixels
+    pub fn set_cube_map_resolution(&mut self, face_size: u32) {
+        let (image_width, image_height) = match self.stereo.map(|stereo| stereo.layout) {
+            Some(StereoLayout::SideBySide) => (12 * face_size, face_size),
+            Some(StereoLayout::TopBottom) => (6 * face_size, 2 * face_size),
+            None => (6 * face_size, face_size),
+        };
+        self.set_resolution(image_width, image_height);
+    }
+
+    pub fn stereo(&self) -> Option<Stereo> {
+        self.stereo
+    }
+
+    /// Splits the image between the eyes, or uses it for a single view if `stereo` is None
+    pub fn set_stereo(&mut self, stereo: Option<Stereo>) {
+        self.stereo = stereo;
+        let (image_width, image_height) = (self.image_width, self.image_height);
+        self.set_resolution(image_width, image_height);
+    }
+
+    pub fn set_super_sampling_n(&mut self, super_sampling_n: u32) {
+        self.super_sampling_n = super_sampling_n;
+    }
+
+    pub fn set_depth_of_field(&mut self, aperture_radius: f64, focal_distance: f64) {
+        self.aperture_radius = aperture_radius;
+        self.focal_distance = focal_distance;
+    }
+
+    /// Moves the focal plane so that `point` is in focus
+    pub fn focus_on(&mut self, point: Vector) {
+        self.focal_distance = (point - self.position) % self.direction;
     }
 
-    pub fn construct_rays_through_pixel(&self, x: u32, y: u32) -> SubPixelRayIterator {
-        SubPixelRayIterator {
-            camera: self,
-            x,
-            y,
-            i: 0,
-            j: 0,
+    /// Constructs a ray through a point of the image, given in (fractional) pixel coordinates.
+    /// `lens_sample` is a point in the unit square, which is mapped to a point on the lens.
+    /// Returns None for points that the camera doesn't see, outside a fisheye image's circle.
+    pub fn construct_ray(&self, image_x: f64, image_y: f64, lens_sample: (f64, f64)) -> Option<Ray> {
+        let (eye_offset, image_x, image_y) = self.eye_view(image_x, image_y);
+        let eye = self.position + self.right * eye_offset;
+        let (origin, direction) = match self.projection {
+            Projection::Perspective => {
+                let screen_point = self.screen_point(image_x, image_y);
+                let target = match self.stereo {
+                    Some(stereo) => self.position + (screen_point - self.position) * (stereo.convergence_distance / self.screen_distance),
+                    None => screen_point,
+                };
+                (eye, eye.direction_to(&target))
+            }
+            Projection::Orthographic => {
+                let screen_point = self.screen_point(image_x, image_y);
+                match self.stereo {
+                    Some(stereo) => {
+                        let origin = screen_point + self.right * eye_offset;
+                        let target = screen_point + self.direction * (stereo.convergence_distance - self.screen_distance);
+                        (origin, origin.direction_to(&target))
+                    }
+                    None => (screen_point, self.direction),
+                }
+            }
+            // Panoramas see all around the camera position, so they have no lens
+            Projection::Equirectangular => return Some(Ray::new(eye, self.equirectangular_direction(image_x, image_y))),
+            Projection::CubeMap => return Some(Ray::new(eye, self.cube_map_direction(image_x, image_y))),
+            Projection::Fisheye { mapping, field_of_view } => {
+                let direction = self.fisheye_direction(image_x, image_y, mapping, field_of_view)?;
+                return Some(Ray::new(eye, direction));
+            }
+        };
+        if self.aperture_radius == 0.0 {
+            return Some(Ray::new(origin, direction));
         }
+
+        // Every ray through the lens that passes through the same point on the focal plane
+        // ends up on the same image point, so only objects on that plane are sharp
+        let distance_to_focal_plane = self.focal_distance - (origin - self.position) % self.direction;
+        let focal_point = origin + direction * (distance_to_focal_plane / (direction % self.direction));
+        let (lens_x, lens_y) = sample_disk(lens_sample);
+        let lens_point = origin
+            + self.right * (lens_x * self.aperture_radius)
+            + self.up * (lens_y * self.aperture_radius);
+        Some(Ray::construct_ray(lens_point, focal_point))
     }
 
-    fn get_rand(&self) -> f64 {
-        if self.super_sampling_n == 1 {
-            0.5
-        } else {
-            rand::random()
+    /// Offset of the eye that sees an image point along `right`, and the point's position in that eye's view
+    fn eye_view(&self, image_x: f64, image_y: f64) -> (f64, f64, f64) {
+        let stereo = match self.stereo {
+            Some(stereo) => stereo,
+            None => return (0.0, image_x, image_y),
+        };
+        let half_distance = stereo.interocular_distance / 2.0;
+        match stereo.layout {
+            StereoLayout::SideBySide if image_x >= self.view_width as f64 => (half_distance, image_x - self.view_width as f64, image_y),
+            StereoLayout::TopBottom if image_y >= self.view_height as f64 => (half_distance, image_x, image_y - self.view_height as f64),
+            _ => (-half_distance, image_x, image_y),
         }
     }
+
+    fn screen_point(&self, image_x: f64, image_y: f64) -> Vector {
+        let mut x_offset = self.pixel_width * image_x - (self.screen_width / 2.0);
+        let mut y_offset = self.pixel_height * image_y - (self.screen_height / 2.0);
+        if let Some(ref distortion) = self.distortion {
+            let (x, y) = distortion.undistort(x_offset / self.screen_distance, y_offset / self.screen_distance);
+            x_offset = x * self.screen_distance;
+            y_offset = y * self.screen_distance;
+        }
+        self.screen_center + self.right * x_offset + self.up * y_offset
+    }
+
+    fn equirectangular_direction(&self, image_x: f64, image_y: f64) -> Vector {
+        let longitude = (image_x / self.view_width as f64 - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - image_y / self.view_height as f64) * PI;
+        // `up` points down the image
+        self.direction * (latitude.cos() * longitude.cos())
+            + self.right * (latitude.cos() * longitude.sin())
+            - self.up * latitude.sin()
+    }
+
+    fn fisheye_direction(&self, image_x: f64, image_y: f64, mapping: FisheyeMapping, field_of_view: f64) -> Option<Vector> {
+        let circle_radius = self.view_width.min(self.view_height) as f64 / 2.0;
+        let x = (image_x - self.view_width as f64 / 2.0) / circle_radius;
+        let y = (image_y - self.view_height as f64 / 2.0) / circle_radius;
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+        let max_angle = field_of_view.to_radians() / 2.0;
+        let angle = match mapping {
+            FisheyeMapping::Equidistant => radius * max_angle,
+            FisheyeMapping::Equisolid => 2.0 * (radius * (max_angle / 2.0).sin()).asin(),
+        };
+        if radius == 0.0 {
+            return Some(self.direction);
+        }
+        let sideways = (self.right * x + self.up * y) * (angle.sin() / radius);
+        Some(self.direction * angle.cos() + sideways)
+    }
+
+    fn cube_map_direction(&self, image_x: f64, image_y: f64) -> Vector {
+        let face_width = self.view_width as f64 / 6.0;
+        let face = ((image_x / face_width) as usize).min(5);
+        // Position on the face, from -1 to 1
+        let a = 2.0 * (image_x - face as f64 * face_width) / face_width - 1.0;
+        let b = 2.0 * image_y / self.view_height as f64 - 1.0;
+        // Forward, right and down directions of each face
+        let (forward, right, down) = match face {
+            0 => (self.right, -self.direction, self.up),
+            1 => (-self.right, self.direction, self.up),
+            2 => (-self.up, self.right, self.direction),
+            3 => (self.up, self.right, -self.direction),
+            4 => (self.direction, self.right, self.up),
+            _ => (-self.direction, -self.right, self.up),
+        };
+        (forward + right * a + down * b).normalized()
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.super_sampling_n.checked_mul(self.super_sampling_n).expect("Super sampling N is above MAX_SUPER_SAMPLING_N")
+    }
+
+    /// Point of the image the sampler's current pixel sample goes through
+    pub fn pixel_sample_position(&self, x: u32, y: u32, sampler: &mut dyn Sampler) -> (f64, f64) {
+        // A single sample goes through the pixel's center
+        let (offset_x, offset_y) = if self.super_sampling_n == 1 {
+            (0.5, 0.5)
+        } else {
+            sampler.sample_2d(FILM_DIMENSION, 0, 1)
+        };
+        (x as f64 + offset_x, y as f64 + offset_y)
+    }
+
+    /// Constructs the ray of the sampler's current pixel sample through the given point of the image.
+    /// Returns None if the camera doesn't see that point of the image.
+    pub fn construct_pixel_sample_ray(&self, image_x: f64, image_y: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let lens_sample = if self.aperture_radius == 0.0 {
+            (0.5, 0.5)
+        } else {
+            sampler.sample_2d(LENS_DIMENSION, 0, 1)
+        };
+        self.construct_ray(image_x, image_y, lens_sample)
+    }
 }
 
-pub struct SubPixelRayIterator<'a> {
-    camera: &'a Camera,
-    x: u32,
-    y: u32,
-    i: u32,
-    j: u32,
+/// Maps a point in the unit square to the unit disk, keeping relative areas
+/// (Shirley and Chiu's concentric mapping)
+fn sample_disk((u, v): (f64, f64)) -> (f64, f64) {
+    let a = 2.0 * u - 1.0;
+    let b = 2.0 * v - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (radius, angle) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+    };
+    (radius * angle.cos(), radius * angle.sin())
 }
 
-impl<'a> Iterator for SubPixelRayIterator<'a> {
-    type Item = Ray;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{self, almost_eq};
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.j >= self.camera.super_sampling_n {
-            self.i += 1;
-            self.j = 0;
+    fn camera(field_of_view: FieldOfView) -> Camera {
+        let position = Vector::new(0.0, 0.0, 0.0);
+        let look_at = Vector::new(0.0, 0.0, 1.0);
+        let up = Vector::new(0.0, 1.0, 0.0);
+        Camera::with_field_of_view(position, look_at, up, field_of_view, 300, 200, 1)
+    }
+
+    fn horizontal_degrees(camera: &Camera) -> f64 {
+        2.0 * (camera.screen_width / 2.0 / camera.screen_distance).atan().to_degrees()
+    }
+
+    #[test]
+    fn test_field_of_view() {
+        let screen = camera(FieldOfView::Screen { distance: 2.0, width: 4.0 });
+        assert!(almost_eq(horizontal_degrees(&screen), 90.0));
+        assert!(almost_eq(screen.screen_height, 4.0 * 2.0 / 3.0));
+
+        assert!(almost_eq(horizontal_degrees(&camera(FieldOfView::Horizontal(60.0))), 60.0));
+
+        let vertical = camera(FieldOfView::Vertical(60.0));
+        let vertical_degrees = 2.0 * (vertical.screen_height / 2.0 / vertical.screen_distance).atan().to_degrees();
+        assert!(almost_eq(vertical_degrees, 60.0));
+
+        // A 36mm wide frame at the focal length, as the image has the frame's aspect ratio
+        let lens = camera(FieldOfView::FocalLength35mm(18.0));
+        assert!(almost_eq(horizontal_degrees(&lens), 90.0));
+
+        // The vertical angle is kept when the aspect ratio changes
+        let mut vertical = vertical;
+        vertical.set_resolution(100, 200);
+        assert!(almost_eq(vertical.screen_height, 2.0 * 30f64.to_radians().tan()));
+    }
+
+    fn angle_from_view_direction(camera: &Camera, image_x: f64, image_y: f64) -> f64 {
+        let ray = camera.construct_ray(image_x, image_y, (0.5, 0.5)).expect("Expected a ray");
+        (ray.direction % camera.direction).acos().to_degrees()
+    }
+
+    #[test]
+    fn test_depth_of_field() {
+        let mut camera = camera(FieldOfView::Horizontal(90.0));
+        let pinhole = camera.construct_ray(80.0, 150.0, (0.5, 0.5)).expect("Expected a ray");
+        camera.set_depth_of_field(0.2, 4.0);
+        let focal_point = pinhole.position + pinhole.direction * (4.0 / (pinhole.direction % camera.direction));
+        // Rays through different points of the lens meet on the focal plane, where the pinhole ray crosses it
+        for &lens_sample in [(0.5, 0.5), (0.1, 0.2), (0.9, 0.4), (0.3, 0.95)].iter() {
+            let ray = camera.construct_ray(80.0, 150.0, lens_sample).expect("Expected a ray");
+            assert!(almost_eq(ray.position % camera.direction, 0.0));
+            assert!((ray.position - camera.position).norm() <= 0.2 + utils::EPSILON);
+            let point = ray.position + ray.direction * (4.0 / (ray.direction % camera.direction));
+            assert!(point.almost_equal_to(&focal_point, utils::EPSILON), "{:?}: {:?}", lens_sample, point);
         }
-        if self.i >= self.camera.super_sampling_n {
-            return None;
+        let center = camera.construct_ray(80.0, 150.0, (0.5, 0.5)).expect("Expected a ray");
+        let edge = camera.construct_ray(80.0, 150.0, (0.9, 0.4)).expect("Expected a ray");
+        assert!(!center.position.almost_equal_to(&edge.position, utils::EPSILON));
+    }
+
+    #[test]
+    fn test_orthographic() {
+        let mut camera = camera(FieldOfView::Horizontal(90.0));
+        camera.projection = Projection::Orthographic;
+        let center = camera.construct_ray(150.0, 100.0, (0.5, 0.5)).expect("Expected a ray");
+        let left = camera.construct_ray(0.0, 100.0, (0.5, 0.5)).expect("Expected a ray");
+        let right = camera.construct_ray(300.0, 100.0, (0.5, 0.5)).expect("Expected a ray");
+        let top = camera.construct_ray(150.0, 0.0, (0.5, 0.5)).expect("Expected a ray");
+        // Parallel rays, starting across the screen
+        for ray in [&center, &left, &right, &top].iter() {
+            assert!(ray.direction.almost_equal_to(&camera.direction, utils::EPSILON));
+            assert!(almost_eq((ray.position - camera.position) % camera.direction, camera.screen_distance));
         }
+        assert!(center.position.almost_equal_to(&camera.screen_center, utils::EPSILON));
+        assert!((right.position - left.position).almost_equal_to(&(camera.right * camera.screen_width), utils::EPSILON));
+        assert!((center.position - top.position).almost_equal_to(&(camera.up * (camera.screen_height / 2.0)), utils::EPSILON));
+    }
 
-        let sub_pixel_x = self.camera.super_sampling_n * self.x + self.i;
-        let sub_pixel_y = self.camera.super_sampling_n * self.y + self.j;
-        let x_offset = self.camera.sub_pixel_width * (sub_pixel_x as f64 + self.camera.get_rand() - 0.5) - (self.camera.screen_width as f64 / 2.0);
-        let y_offset = self.camera.sub_pixel_height * (sub_pixel_y as f64 + self.camera.get_rand() - 0.5) - (self.camera.screen_height as f64 / 2.0);
-        let x_delta = self.camera.right * x_offset;
-        let y_delta = self.camera.up * y_offset;
-        let sub_cell_point = self.camera.screen_center + x_delta + y_delta;
-        let ray = Ray::construct_ray(self.camera.position, sub_cell_point);
-        self.j += 1;
-        Some(ray)
+    #[test]
+    fn test_fisheye() {
+        for &mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid].iter() {
+            let mut camera = camera(FieldOfView::Horizontal(90.0));
+            camera.projection = Projection::Fisheye { mapping, field_of_view: 180.0 };
+            assert!(almost_eq(angle_from_view_direction(&camera, 150.0, 100.0), 0.0));
+            assert!(almost_eq(angle_from_view_direction(&camera, 150.0, 0.0), 90.0));
+            assert!(almost_eq(angle_from_view_direction(&camera, 250.0, 100.0), 90.0));
+            assert!(camera.construct_ray(10.0, 100.0, (0.5, 0.5)).is_none());
+        }
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        let len = (self.camera.super_sampling_n * self.camera.super_sampling_n) as usize;
-        (len, Some(len))
+    #[test]
+    fn test_equirectangular() {
+        let mut camera = camera(FieldOfView::Horizontal(90.0));
+        camera.projection = Projection::Equirectangular;
+        camera.set_resolution(400, 200);
+        let direction = |image_x: f64, image_y: f64| camera.construct_ray(image_x, image_y, (0.5, 0.5)).expect("Expected a ray").direction;
+        let world_up = Vector::new(0.0, 1.0, 0.0);
+        assert!(direction(200.0, 100.0).almost_equal_to(&camera.direction, utils::EPSILON));
+        // A quarter of the width is 90 degrees to either side
+        assert!(direction(100.0, 100.0).almost_equal_to(&-camera.right, utils::EPSILON));
+        assert!(direction(300.0, 100.0).almost_equal_to(&camera.right, utils::EPSILON));
+        // The whole top row looks straight up
+        for &image_x in [0.0, 150.0, 399.0].iter() {
+            assert!(direction(image_x, 0.0).almost_equal_to(&world_up, utils::EPSILON));
+        }
+        assert!(direction(200.0, 200.0).almost_equal_to(&-world_up, utils::EPSILON));
+    }
+
+    #[test]
+    fn test_cube_map() {
+        let mut camera = camera(FieldOfView::Horizontal(90.0));
+        camera.projection = Projection::CubeMap;
+        camera.set_resolution(600, 100);
+        assert!(camera.has_cube_map_aspect());
+        let faces = [
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(-1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(0.0, -1.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(0.0, 0.0, -1.0),
+        ];
+        for (face, expected) in faces.iter().enumerate() {
+            // The center of each face looks straight at it, and its corners 45 degrees to the side
+            let center = camera.construct_ray(face as f64 * 100.0 + 50.0, 50.0, (0.5, 0.5)).expect("Expected a ray");
+            assert!(center.direction.almost_equal_to(expected, utils::EPSILON), "face {}: {:?}", face, center.direction);
+            let corner = camera.construct_ray(face as f64 * 100.0, 0.0, (0.5, 0.5)).expect("Expected a ray");
+            assert!(almost_eq((corner.direction % *expected).acos().to_degrees(), 54.735_610_317_245_35));
+        }
+        // Other aspect ratios still show every face
+        camera.set_resolution(300, 300);
+        assert!(!camera.has_cube_map_aspect());
+        let back = camera.construct_ray(275.0, 150.0, (0.5, 0.5)).expect("Expected a ray");
+        assert!(back.direction.almost_equal_to(&faces[5], utils::EPSILON));
     }
-}
 
-impl<'a> ExactSizeIterator for SubPixelRayIterator<'a> {}
+    #[test]
+    fn test_stereo() {
+        let mut camera = camera(FieldOfView::Horizontal(90.0));
+        camera.set_resolution(600, 300);
+        camera.set_stereo(Some(Stereo { interocular_distance: 0.2, convergence_distance: 5.0, layout: StereoLayout::SideBySide }));
+        // Each eye sees a square view
+        assert!(almost_eq(camera.screen_height, camera.screen_width));
+
+        // The same point of both views is on the same point of the convergence plane
+        let left = camera.construct_ray(100.0, 120.0, (0.5, 0.5)).expect("Expected a ray");
+        let right = camera.construct_ray(400.0, 120.0, (0.5, 0.5)).expect("Expected a ray");
+        assert!(almost_eq((right.position - left.position).norm(), 0.2));
+        assert!(almost_eq((right.position - left.position) % camera.right, 0.2));
+        let left_point = left.position + left.direction * (5.0 / (left.direction % camera.direction));
+        let right_point = right.position + right.direction * (5.0 / (right.direction % camera.direction));
+        assert!(almost_eq((left_point - right_point).norm(), 0.0));
+    }
+
+    #[test]
+    fn test_lens_distortion() {
+        let distortion = LensDistortion { k1: -0.3, k2: 0.1, p1: 0.01, p2: -0.02, k3: 0.0 };
+        let (x, y) = distortion.undistort(0.4, -0.3);
+        let (distorted_x, distorted_y) = distortion.distort(x, y);
+        assert!(almost_eq(distorted_x, 0.4) && almost_eq(distorted_y, -0.3));
+
+        // Barrel distortion squeezes a wider view into the image
+        let mut camera = camera(FieldOfView::Horizontal(90.0));
+        let undistorted_angle = angle_from_view_direction(&camera, 300.0, 100.0);
+        camera.distortion = Some(LensDistortion { k1: -0.2, k2: 0.0, p1: 0.0, p2: 0.0, k3: 0.0 });
+        assert!(angle_from_view_direction(&camera, 300.0, 100.0) > undistorted_angle);
+    }
+}
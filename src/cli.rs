@@ -0,0 +1,236 @@
+use rust_renderer::camera::MAX_SUPER_SAMPLING_N;
+use rust_renderer::sampler::SamplerKind;
+use rust_renderer::filter::FilterKind;
+use rust_renderer::scene::RenderProgress;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+Usage: rust_renderer [OPTIONS] <SCENE>
+
+Renders a scene file to an image.
+
+Options:
+  -o, --output <PATH>         Output image path [default: output.png]
+      --width <PIXELS>        Override the scene's image width
+      --height <PIXELS>       Override the scene's image height (if only one of width and
+                              height is given, the other follows the scene's aspect ratio)
+  -j, --threads <N>           Number of render threads [default: number of CPUs]
+      --tile-size <PIXELS>    Override the size of the square tiles the threads render
+      --super-sampling <N>    Override the scene's super sampling (N x N rays per pixel)
+      --shadow-rays <N>       Override the scene's shadow rays (N x N rays per light)
+      --max-recursion <N>     Override the scene's max recursion level
+      --adaptive <THRESHOLD>  Stop sampling pixels once the standard error of their
+                              luminance is below THRESHOLD (super sampling is the maximum)
+      --heatmap <PATH>        Also write an image of the number of samples taken per pixel
+      --seed <N>              Override the scene's random seed
+      --sampler <NAME>        Override the scene's sampler: independent, stratified, halton or sobol
+      --filter <NAME>         Override the scene's reconstruction filter: box, tent, gaussian,
+                              mitchell or lanczos
+      --filter-radius <PIXELS>
+                              Override the filter's radius [default: the filter's own]
+  -q, --quiet                 Only print errors
+      --progress              Print progress while loading and rendering
+  -h, --help                  Print this help
+
+Exit codes:
+  0  success
+  2  invalid command line
+  3  the scene file has errors
+  4  reading the scene or writing the image failed
+  5  rendering failed";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Progress,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub scene_path: PathBuf,
+    pub output_path: PathBuf,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub thread_count: Option<usize>,
+    pub tile_size: Option<u32>,
+    pub super_sampling_n: Option<u32>,
+    pub shadow_rays_n: Option<u32>,
+    pub max_recursion: Option<u32>,
+    pub noise_threshold: Option<f64>,
+    pub heatmap_path: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub sampler: Option<SamplerKind>,
+    pub filter: Option<FilterKind>,
+    pub filter_radius: Option<f64>,
+    pub verbosity: Verbosity,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Render(Options),
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+pub fn parse_args<I: IntoIterator<Item=String>>(args: I) -> Result<Command, UsageError> {
+    let mut scene_path: Option<PathBuf> = None;
+    let mut options = Options {
+        scene_path: PathBuf::new(),
+        output_path: PathBuf::from("output.png"),
+        width: None,
+        height: None,
+        thread_count: None,
+        tile_size: None,
+        super_sampling_n: None,
+        shadow_rays_n: None,
+        max_recursion: None,
+        noise_threshold: None,
+        heatmap_path: None,
+        seed: None,
+        sampler: None,
+        filter: None,
+        filter_radius: None,
+        verbosity: Verbosity::Normal,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // Accept both "--name value" and "--name=value"
+        let (name, mut inline_value) = match arg.find('=') {
+            Some(index) if arg.starts_with("--") => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| -> Result<String, UsageError> {
+            inline_value.take()
+                .or_else(|| args.next())
+                .ok_or_else(|| UsageError(format!("missing value for {}", name)))
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => options.output_path = PathBuf::from(value(&name)?),
+            "--width" => options.width = Some(parse_positive(&name, &value(&name)?)?),
+            "--height" => options.height = Some(parse_positive(&name, &value(&name)?)?),
+            "-j" | "--threads" => options.thread_count = Some(parse_positive(&name, &value(&name)?)?),
+            "--tile-size" => options.tile_size = Some(parse_positive(&name, &value(&name)?)?),
+            "--super-sampling" => {
+                let super_sampling_n = parse_positive(&name, &value(&name)?)?;
+                if super_sampling_n > MAX_SUPER_SAMPLING_N {
+                    return Err(UsageError(format!("{} must be at most {}", name, MAX_SUPER_SAMPLING_N)));
+                }
+                options.super_sampling_n = Some(super_sampling_n);
+            }
+            "--shadow-rays" => options.shadow_rays_n = Some(parse_positive(&name, &value(&name)?)?),
+            "--max-recursion" => options.max_recursion = Some(parse_value(&name, &value(&name)?)?),
+            "--adaptive" => options.noise_threshold = Some(parse_positive(&name, &value(&name)?)?),
+            "--heatmap" => options.heatmap_path = Some(PathBuf::from(value(&name)?)),
+            "--seed" => options.seed = Some(parse_value(&name, &value(&name)?)?),
+            "--sampler" => options.sampler = Some(parse_value(&name, &value(&name)?)?),
+            "--filter" => options.filter = Some(parse_value(&name, &value(&name)?)?),
+            "--filter-radius" => options.filter_radius = Some(parse_positive(&name, &value(&name)?)?),
+            "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+            "--progress" => options.verbosity = Verbosity::Progress,
+            _ if name.starts_with('-') && name.len() > 1 => {
+                return Err(UsageError(format!("unknown option {}", name)));
+            }
+            _ => {
+                if scene_path.is_some() {
+                    return Err(UsageError(format!("unexpected argument {}", arg)));
+                }
+                scene_path = Some(PathBuf::from(arg));
+            }
+        }
+        if inline_value.is_some() {
+            return Err(UsageError(format!("{} does not take a value", name)));
+        }
+    }
+
+    options.scene_path = scene_path.ok_or_else(|| UsageError("missing scene file path".to_string()))?;
+    Ok(Command::Render(options))
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, UsageError> {
+    value.parse().map_err(|_| UsageError(format!("invalid value '{}' for {}", value, name)))
+}
+
+fn parse_positive<T: FromStr + PartialOrd + Default>(name: &str, value: &str) -> Result<T, UsageError> {
+    let parsed: T = parse_value(name, value)?;
+    if parsed <= T::default() {
+        return Err(UsageError(format!("{} must be positive", name)));
+    }
+    Ok(parsed)
+}
+
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// e.g. "[=========                     ]  30%  12/40 tiles  ETA 0:07"
+pub fn progress_bar(progress: &RenderProgress) -> String {
+    let filled = ((progress.fraction * PROGRESS_BAR_WIDTH as f64).round() as usize).min(PROGRESS_BAR_WIDTH);
+    let remaining_seconds = progress.remaining.as_secs();
+    format!("[{}{}] {:3.0}%  {}/{} tiles  ETA {}:{:02}",
+            "=".repeat(filled), " ".repeat(PROGRESS_BAR_WIDTH - filled), progress.fraction * 100.0,
+            progress.finished_tiles, progress.total_tiles, remaining_seconds / 60, remaining_seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn parse(args: &[&str]) -> Result<Command, UsageError> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let command = parse(&["scene.txt", "-o", "out.png", "--width=640", "--height", "480", "-j", "3", "--max-recursion", "0", "--seed=7", "--sampler", "sobol", "--filter=mitchell", "-q"]);
+        assert_eq!(command, Ok(Command::Render(Options {
+            scene_path: PathBuf::from("scene.txt"),
+            output_path: PathBuf::from("out.png"),
+            width: Some(640),
+            height: Some(480),
+            thread_count: Some(3),
+            tile_size: None,
+            super_sampling_n: None,
+            shadow_rays_n: None,
+            max_recursion: Some(0),
+            noise_threshold: None,
+            heatmap_path: None,
+            seed: Some(7),
+            sampler: Some(SamplerKind::Sobol),
+            filter: Some(FilterKind::Mitchell),
+            filter_radius: None,
+            verbosity: Verbosity::Quiet,
+        })));
+        assert_eq!(parse(&["--help", "scene.txt"]), Ok(Command::Help));
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["scene.txt", "--threads", "0"]).is_err());
+        assert!(parse(&["scene.txt", "--super-sampling", "65535"]).is_ok());
+        assert!(parse(&["scene.txt", "--super-sampling", "65536"]).is_err());
+        assert!(parse(&["scene.txt", "--width"]).is_err());
+        assert!(parse(&["scene.txt", "--quiet=yes"]).is_err());
+        assert!(parse(&["scene.txt", "--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        let progress = RenderProgress {
+            finished_tiles: 12,
+            total_tiles: 40,
+            fraction: 0.3,
+            elapsed: Duration::from_secs(3),
+            remaining: Duration::from_millis(67500),
+        };
+        assert_eq!(progress_bar(&progress), "[=========                     ]  30%  12/40 tiles  ETA 1:07");
+    }
+}
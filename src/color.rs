@@ -17,17 +17,22 @@ pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0 };
 
 impl Color {
     pub fn new(r: f64, g: f64, b: f64) -> Color {
-        Color { r: r, g: g, b: b }
+        Color { r, g, b }
     }
 
     pub fn clamped(self) -> Color {
         Color {
-            r: self.r.max(0.0).min(1.0),
-            g: self.g.max(0.0).min(1.0),
-            b: self.b.max(0.0).min(1.0)
+            r: self.r.clamp(0.0, 1.0),
+            g: self.g.clamp(0.0, 1.0),
+            b: self.b.clamp(0.0, 1.0)
         }
     }
 
+    /// Perceived brightness (Rec. 709 weights)
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     pub fn bytes(self) -> (u8, u8, u8) {
         let c = self.clamped() * 255f64;
         (c.r.round() as u8, c.g.round() as u8, c.b.round() as u8)
@@ -10,14 +10,25 @@ pub struct ColorImage {
 
 impl ColorImage {
     pub fn new(width: u32, height: u32) -> ColorImage {
-        let size = (height * width) as usize;
+        let size = height as usize * width as usize;
         ColorImage {
             pixels: vec![BLACK; size],
-            height: height,
-            width: width,
+            height,
+            width,
         }
     }
 
+    /// Visualizes per pixel values (row by row), from black for 0 through red and yellow to
+    /// white for `max_value`
+    pub fn heatmap(values: &[u32], width: u32, height: u32, max_value: u32) -> ColorImage {
+        let mut color_image = ColorImage::new(width, height);
+        for (pixel, &value) in color_image.pixels.iter_mut().zip(values.iter()) {
+            let t = value as f64 / max_value.max(1) as f64;
+            *pixel = Color::new(3.0 * t, 3.0 * t - 1.0, 3.0 * t - 2.0).clamped();
+        }
+        color_image
+    }
+
     pub fn to_image_buffer(&self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
         ImageBuffer::<Rgb<u8>, Vec<u8>>::from_fn(self.width, self.height, |x, y| {
             let (r, g, b) = self[(x as usize, y as usize)].bytes();
@@ -31,13 +42,13 @@ impl Index<(usize, usize)> for ColorImage {
 
     fn index(&self, index: (usize, usize)) -> &Color {
         let (x, y) = index;
-        &self.pixels[(y * self.width as usize + x)]
+        &self.pixels[y * self.width as usize + x]
     }
 }
 
 impl IndexMut<(usize, usize)> for ColorImage {
-    fn index_mut<'a>(&'a mut self, index: (usize, usize)) -> &'a mut Color {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Color {
         let (x, y) = index;
-        &mut self.pixels[(y * self.width as usize + x)]
+        &mut self.pixels[y * self.width as usize + x]
     }
 }
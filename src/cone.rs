@@ -0,0 +1,131 @@
+use model_object::ModelObject;
+use vector::Vector;
+use ray::Ray;
+use hit::{self, Hit, Interval};
+use material::Material;
+use bounding_box::BoundingBox;
+use disk::Disk;
+use utils;
+use std::option::Option::{None, Some};
+
+/// Cone from `apex` widening to a circle of `base_radius` around `base`, optionally closed by a
+/// disk at the base
+#[derive(Debug, Copy, Clone)]
+pub struct Cone {
+    pub material: Material,
+    pub apex: Vector,
+    pub base: Vector,
+    pub base_radius: f64,
+    /// Unit direction from apex to base
+    axis: Vector,
+    height: f64,
+    cap: Option<Disk>,
+}
+
+impl Cone {
+    pub fn new(apex: Vector, base: Vector, base_radius: f64, capped: bool, material: Material) -> Cone {
+        let axis = (base - apex).normalized();
+        Cone {
+            material,
+            apex,
+            base,
+            base_radius,
+            axis,
+            height: apex.distance_to(&base),
+            cap: if capped { Some(Disk::new(base, axis, base_radius, material)) } else { None },
+        }
+    }
+
+    /// Where the ray's whole line crosses the side and the cap, as (distance, outward normal)
+    fn crossings(&self, ray: &Ray) -> [Option<(f64, Vector)>; 3] {
+        // The side is where the distance from the axis is slope * (height along the axis),
+        // which also solves for the mirrored cone above the apex, so heights are checked
+        let slope = self.base_radius / self.height;
+        let slope_squared = slope * slope;
+        let offset = ray.position - self.apex;
+        let offset_height = offset % self.axis;
+        let direction_height = ray.direction % self.axis;
+        let direction_perpendicular = ray.direction - self.axis * direction_height;
+        let offset_perpendicular = offset - self.axis * offset_height;
+        let roots = utils::solve_quadratic(
+            direction_perpendicular % direction_perpendicular - slope_squared * direction_height * direction_height,
+            2.0 * (direction_perpendicular % offset_perpendicular - slope_squared * offset_height * direction_height),
+            offset_perpendicular % offset_perpendicular - slope_squared * offset_height * offset_height,
+        );
+        let side_crossing = |distance: f64| {
+            let height = offset_height + direction_height * distance;
+            if height < 0.0 || height > self.height {
+                return None;
+            }
+            let radial = offset_perpendicular + direction_perpendicular * distance;
+            // Tilted towards the apex by the slope; the apex itself only has the axis
+            let normal = if radial.norm_squared() == 0.0 {
+                -self.axis
+            } else {
+                (radial.normalized() - self.axis * slope).normalized()
+            };
+            Some((distance, normal))
+        };
+        [
+            roots.and_then(|(near, _)| side_crossing(near)),
+            roots.and_then(|(_, far)| side_crossing(far)),
+            self.cap.and_then(|cap| cap.intersect_line(ray).map(|distance| (distance, cap.normal))),
+        ]
+    }
+}
+
+impl ModelObject for Cone {
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let (distance, normal) = hit::nearest_hit(&self.crossings(ray))?;
+        let hit_point = ray.position + ray.direction * distance;
+        Some(Hit::new(ray, distance, normal, hit_point, self))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let base = Disk::new(self.base, self.axis, self.base_radius, self.material).bounding_box()?;
+        Some(base.including(&self.apex))
+    }
+
+    fn is_closed(&self) -> bool {
+        // Only closed by the cap
+        self.cap.is_some()
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<Interval<'_>>> {
+        self.cap?;
+        let crossings = self.crossings(ray).iter().filter_map(|&crossing| crossing).collect();
+        Some(hit::intervals_between(self, crossings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::almost_eq;
+
+    #[test]
+    fn test_cone_hits() {
+        // Apex at the top, 45 degree sides
+        let cone = Cone::new(Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, 0.0), 2.0, true, Material::default());
+        let hit = cone.try_hit(&Ray::new(Vector::new(-3.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 2.0));
+        assert!(hit.hit_normal.almost_equal_to(&Vector::new(-1.0, 1.0, 0.0).normalized(), utils::EPSILON));
+
+        // The base, and the mirrored cone above the apex isn't part of it
+        let up = Ray::new(Vector::new(0.5, -1.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        let hit = cone.try_hit(&up).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 1.0));
+        assert_eq!(hit.hit_normal, Vector::new(0.0, -1.0, 0.0));
+        assert!(cone.try_hit(&Ray::new(Vector::new(-3.0, 3.0, 0.0), Vector::new(1.0, 0.0, 0.0))).is_none());
+
+        // Without the base, a ray from below hits the inside of the side
+        let open = Cone::new(Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, 0.0), 2.0, false, Material::default());
+        let hit = open.try_hit(&up).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 2.5));
+        assert!(!hit.entering);
+    }
+}
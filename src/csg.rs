@@ -0,0 +1,203 @@
+use model_object::ModelObject;
+use ray::Ray;
+use hit::{Hit, Crossing, Interval};
+use material::Material;
+use bounding_box::BoundingBox;
+use std::str::FromStr;
+use std::option::Option::{None, Some};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// The left operand without the right one
+    Difference,
+}
+
+impl CsgOperation {
+    /// Whether a point inside or outside of each operand is inside the result
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+impl FromStr for CsgOperation {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<CsgOperation, ()> {
+        match name {
+            "union" => Ok(CsgOperation::Union),
+            "intersection" => Ok(CsgOperation::Intersection),
+            "difference" => Ok(CsgOperation::Difference),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Constructive solid geometry: a solid combining two closed objects, which may be CSG nodes
+/// themselves. Hits are on the operands' surfaces, so each part keeps its operand's material.
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Box<dyn ModelObject + Send + Sync>,
+    pub right: Box<dyn ModelObject + Send + Sync>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation,
+               left: Box<dyn ModelObject + Send + Sync>,
+               right: Box<dyn ModelObject + Send + Sync>) -> Csg {
+        Csg {
+            operation,
+            left,
+            right,
+        }
+    }
+}
+
+impl ModelObject for Csg {
+    fn material(&self) -> Material {
+        self.left.material()
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        // The first crossing in front of the ray; infinite distances are the open ends of half spaces
+        let intervals = self.intervals(ray)?;
+        let crossing = intervals.iter()
+            .flat_map(|interval| vec![interval.entry, interval.exit])
+            .find(|crossing| crossing.distance >= 0.0 && crossing.distance.is_finite())?;
+        let hit_point = ray.position + ray.direction * crossing.distance;
+        Some(Hit::new(ray, crossing.distance, crossing.normal, hit_point, crossing.object))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+        match self.operation {
+            CsgOperation::Union => match (left, right) {
+                (Some(left), Some(right)) => Some(left.union(&right)),
+                _ => None,
+            },
+            CsgOperation::Intersection => match (left, right) {
+                (Some(left), Some(right)) => Some(left.intersection(&right)),
+                (left, None) => left,
+                (None, right) => right,
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.left.is_closed() && self.right.is_closed()
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<Interval<'_>>> {
+        let left = self.left.intervals(ray)?;
+        let right = self.right.intervals(ray)?;
+        // Walk along the line through the operands' crossings as (crossing, entering, of left),
+        // keeping track of which operands it is inside
+        let mut events: Vec<(Crossing, bool, bool)> = Vec::new();
+        for &(intervals, of_left) in &[(&left, true), (&right, false)] {
+            for interval in intervals.iter() {
+                events.push((interval.entry, true, of_left));
+                events.push((interval.exit, false, of_left));
+            }
+        }
+        events.sort_by(|a, b| a.0.distance.total_cmp(&b.0.distance));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut entry: Option<Crossing> = None;
+        let mut intervals = Vec::new();
+        for (index, &(crossing, entering, of_left)) in events.iter().enumerate() {
+            if of_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+            // Coinciding surfaces are crossed at once, so e.g. subtracting an object from itself leaves nothing
+            if events.get(index + 1).is_some_and(|next| next.0.distance == crossing.distance) {
+                continue;
+            }
+            let inside = self.operation.contains(in_left, in_right);
+            if inside == entry.is_some() {
+                continue;
+            }
+            // Entering the result by leaving the subtracted operand, or the other way around,
+            // turns that operand's surface inside out
+            let crossing = if inside == entering { crossing } else { Crossing { normal: -crossing.normal, ..crossing } };
+            match entry.take() {
+                Some(entry) => intervals.push(Interval { entry, exit: crossing }),
+                None => entry = Some(crossing),
+            }
+        }
+        Some(intervals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vector::Vector;
+    use sphere::Sphere;
+    use cuboid::Cuboid;
+    use plane::Plane;
+    use utils::{self, almost_eq};
+
+    fn unit_sphere(x: f64) -> Box<dyn ModelObject + Send + Sync> {
+        Box::new(Sphere { material: Material::default(), center: Vector::new(x, 0.0, 0.0), radius: 1.0 })
+    }
+
+    #[test]
+    fn test_operations() {
+        // Two spheres overlapping between x = 0 and x = 1, seen along the x axis
+        let along_x = Ray::new(Vector::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let spans = |operation| {
+            let csg = Csg::new(operation, unit_sphere(0.0), unit_sphere(1.0));
+            csg.intervals(&along_x).unwrap().iter()
+                .map(|interval| (interval.entry.distance, interval.exit.distance))
+                .collect::<Vec<(f64, f64)>>()
+        };
+        assert_eq!(spans(CsgOperation::Union), vec![(4.0, 7.0)]);
+        assert_eq!(spans(CsgOperation::Intersection), vec![(5.0, 6.0)]);
+        assert_eq!(spans(CsgOperation::Difference), vec![(4.0, 5.0)]);
+
+        // Hitting the inside of the subtracted sphere, whose normal now faces out of the result
+        let difference = Csg::new(CsgOperation::Difference, unit_sphere(0.0), unit_sphere(1.0));
+        let hit = difference.try_hit(&Ray::new(Vector::new(0.5, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 0.5));
+        assert!(hit.entering);
+        assert!(hit.hit_normal.almost_equal_to(&Vector::new(1.0, 0.0, 0.0), utils::EPSILON));
+        assert!(difference.try_hit(&Ray::new(Vector::new(0.5, -5.0, 0.0), Vector::new(0.0, 1.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn test_nested_with_half_space() {
+        // A box with a sphere carved out of its top face, cut in half by the x = 0 plane
+        let cuboid = Cuboid::new(Vector::new(-1.0, -1.0, -1.0), Vector::new(1.0, 1.0, 1.0), Vector::new(0.0, 0.0, 0.0), Material::default());
+        let carved = Csg::new(CsgOperation::Difference, Box::new(cuboid), Box::new(Sphere {
+            material: Material::default(),
+            center: Vector::new(0.0, 1.0, 0.0),
+            radius: 0.5,
+        }));
+        let half_space = Plane { material: Material::default(), normal: Vector::new(1.0, 0.0, 0.0), offset: 0.0 };
+        let csg = Csg::new(CsgOperation::Intersection, Box::new(carved), Box::new(half_space));
+
+        let down = Vector::new(0.0, -1.0, 0.0);
+        let hit = csg.try_hit(&Ray::new(Vector::new(-0.2, 5.0, 0.0), down)).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 4.0 + 0.21f64.sqrt()));
+        assert!(csg.try_hit(&Ray::new(Vector::new(0.2, 5.0, 0.0), down)).is_none());
+        // The cut face, from outside and from inside
+        let hit = csg.try_hit(&Ray::new(Vector::new(3.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 3.0));
+        assert_eq!(hit.hit_normal, Vector::new(1.0, 0.0, 0.0));
+        let hit = csg.try_hit(&Ray::new(Vector::new(-0.5, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 0.5));
+        assert!(!hit.entering);
+
+        let bounding_box = csg.bounding_box().unwrap();
+        assert_eq!(bounding_box.max, Vector::new(1.0, 1.0, 1.0));
+    }
+}
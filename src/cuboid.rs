@@ -0,0 +1,161 @@
+use model_object::ModelObject;
+use vector::Vector;
+use ray::Ray;
+use hit::{self, Hit, Interval};
+use material::Material;
+use bounding_box::BoundingBox;
+use transform::Transform;
+use std::f64;
+use std::option::Option::{None, Some};
+
+/// Box between two corners, optionally rotated around its center
+/// (not called Box, so that it doesn't shadow std's Box)
+#[derive(Debug, Copy, Clone)]
+pub struct Cuboid {
+    pub material: Material,
+    /// Half of the size along each of the box's own axes
+    half_size: Vector,
+    /// From the box's own frame, centered on the box, to the scene
+    transform: Transform,
+}
+
+impl Cuboid {
+    /// `rotation_degrees` are as in `Transform::new`
+    pub fn new(corner1: Vector, corner2: Vector, rotation_degrees: Vector, material: Material) -> Cuboid {
+        let bounds = BoundingBox::new(corner1, corner2);
+        Cuboid {
+            material,
+            half_size: bounds.extent() * 0.5,
+            transform: Transform::new(bounds.centroid(), 1.0, rotation_degrees),
+        }
+    }
+
+    /// Where the ray's whole line enters and leaves the box, as (distance, outward normal)
+    fn slabs(&self, ray: &Ray) -> Option<((f64, Vector), (f64, Vector))> {
+        // Slab test in the box's frame, keeping the axes of the faces the line enters and leaves through
+        let position = self.transform.invert_point(&ray.position);
+        let direction = self.transform.invert_direction(&ray.direction);
+        let (mut t_near, mut near_axis) = (f64::NEG_INFINITY, 0);
+        let (mut t_far, mut far_axis) = (f64::INFINITY, 0);
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                if position[axis].abs() > self.half_size[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (-self.half_size[axis] - position[axis]) / direction[axis];
+            let t2 = (self.half_size[axis] - position[axis]) / direction[axis];
+            if t1.min(t2) > t_near {
+                t_near = t1.min(t2);
+                near_axis = axis;
+            }
+            if t1.max(t2) < t_far {
+                t_far = t1.max(t2);
+                far_axis = axis;
+            }
+        }
+        if t_near > t_far {
+            return None;
+        }
+        let face_normal = |axis: usize, sign: f64| {
+            let normal = match axis {
+                0 => Vector::new(sign, 0.0, 0.0),
+                1 => Vector::new(0.0, sign, 0.0),
+                _ => Vector::new(0.0, 0.0, sign),
+            };
+            self.transform.apply_to_direction(&normal)
+        };
+        Some((
+            (t_near, face_normal(near_axis, -direction[near_axis].signum())),
+            (t_far, face_normal(far_axis, direction[far_axis].signum())),
+        ))
+    }
+}
+
+impl ModelObject for Cuboid {
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let (near, far) = self.slabs(ray)?;
+        // If the ray starts inside the box, the hit is where it leaves
+        let (distance, normal) = if near.0 >= 0.0 {
+            near
+        } else if far.0 >= 0.0 {
+            far
+        } else {
+            return None;
+        };
+        let hit_point = ray.position + ray.direction * distance;
+        Some(Hit::new(ray, distance, normal, hit_point, self))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let h = self.half_size;
+        let corners = (0..8).map(|corner| {
+            let x = if corner & 1 == 0 { -h.x } else { h.x };
+            let y = if corner & 2 == 0 { -h.y } else { h.y };
+            let z = if corner & 4 == 0 { -h.z } else { h.z };
+            self.transform.apply_to_point(&Vector::new(x, y, z))
+        });
+        Some(BoundingBox::from_points(corners))
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<Interval<'_>>> {
+        let crossings = match self.slabs(ray) {
+            Some((near, far)) => vec![near, far],
+            None => Vec::new(),
+        };
+        Some(hit::intervals_between(self, crossings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::almost_eq;
+
+    fn unit_box(rotation_degrees: Vector) -> Cuboid {
+        Cuboid::new(Vector::new(1.0, 1.0, 1.0), Vector::new(-1.0, -1.0, -1.0), rotation_degrees, Material::default())
+    }
+
+    #[test]
+    fn test_face_hits() {
+        let cuboid = unit_box(Vector::new(0.0, 0.0, 0.0));
+        let hit = cuboid.try_hit(&Ray::new(Vector::new(0.5, -3.0, 0.2), Vector::new(0.0, 1.0, 0.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 2.0));
+        assert!(hit.entering);
+        assert_eq!(hit.hit_normal, Vector::new(0.0, -1.0, 0.0));
+
+        // From inside, the hit is on the far face, with the normal facing the ray
+        let hit = cuboid.try_hit(&Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 1.0));
+        assert!(!hit.entering);
+        assert_eq!(hit.hit_normal, Vector::new(0.0, 0.0, -1.0));
+
+        // Missing, and behind the ray
+        assert!(cuboid.try_hit(&Ray::new(Vector::new(1.5, -3.0, 0.0), Vector::new(0.0, 1.0, 0.0))).is_none());
+        assert!(cuboid.try_hit(&Ray::new(Vector::new(0.0, 3.0, 0.0), Vector::new(0.0, 1.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn test_rotated_box() {
+        let cuboid = unit_box(Vector::new(0.0, 0.0, 45.0));
+        // The edge between two faces now points along -x
+        let hit = cuboid.try_hit(&Ray::new(Vector::new(-3.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 3.0 - 2f64.sqrt()));
+        assert!(cuboid.try_hit(&Ray::new(Vector::new(-3.0, 1.2, 0.0), Vector::new(1.0, 0.0, 0.0))).is_some());
+        let hit = cuboid.try_hit(&Ray::new(Vector::new(-3.0, 0.5, 0.0), Vector::new(1.0, 0.0, 0.0))).expect("Expected a hit");
+        assert!(hit.hit_normal.almost_equal_to(&Vector::new(-1.0, 1.0, 0.0).normalized(), ::utils::EPSILON));
+
+        let bounding_box = cuboid.bounding_box().unwrap();
+        assert!(almost_eq(bounding_box.max.x, 2f64.sqrt()));
+        assert!(almost_eq(bounding_box.max.z, 1.0));
+    }
+}
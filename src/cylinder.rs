@@ -0,0 +1,129 @@
+use model_object::ModelObject;
+use vector::Vector;
+use ray::Ray;
+use hit::{self, Hit, Interval};
+use material::Material;
+use bounding_box::BoundingBox;
+use disk::Disk;
+use utils;
+use std::option::Option::{None, Some};
+
+/// Cylinder around the segment from `base` to `top`, optionally closed by disks at both ends
+#[derive(Debug, Copy, Clone)]
+pub struct Cylinder {
+    pub material: Material,
+    pub base: Vector,
+    pub top: Vector,
+    pub radius: f64,
+    /// Unit direction from base to top
+    axis: Vector,
+    height: f64,
+    caps: Option<[Disk; 2]>,
+}
+
+impl Cylinder {
+    pub fn new(base: Vector, top: Vector, radius: f64, capped: bool, material: Material) -> Cylinder {
+        let axis = (top - base).normalized();
+        let caps = if capped {
+            Some([Disk::new(base, -axis, radius, material), Disk::new(top, axis, radius, material)])
+        } else {
+            None
+        };
+        Cylinder {
+            material,
+            base,
+            top,
+            radius,
+            axis,
+            height: base.distance_to(&top),
+            caps,
+        }
+    }
+
+    /// Where the ray's whole line crosses the side and the caps, as (distance, outward normal)
+    fn crossings(&self, ray: &Ray) -> [Option<(f64, Vector)>; 4] {
+        // The side is where the distance from the axis is the radius, i.e. where the parts of
+        // the ray perpendicular to the axis add up to a vector of length radius
+        let offset = ray.position - self.base;
+        let direction_perpendicular = ray.direction - self.axis * (ray.direction % self.axis);
+        let offset_perpendicular = offset - self.axis * (offset % self.axis);
+        let roots = utils::solve_quadratic(
+            direction_perpendicular % direction_perpendicular,
+            2.0 * (direction_perpendicular % offset_perpendicular),
+            offset_perpendicular % offset_perpendicular - self.radius * self.radius,
+        );
+        let side_crossing = |distance: f64| {
+            let height = (offset + ray.direction * distance) % self.axis;
+            if height < 0.0 || height > self.height {
+                return None;
+            }
+            let normal = (offset_perpendicular + direction_perpendicular * distance) * (1.0 / self.radius);
+            Some((distance, normal))
+        };
+        let cap_crossing = |index: usize| self.caps.and_then(|caps| caps[index].intersect_line(ray).map(|distance| (distance, caps[index].normal)));
+        [
+            roots.and_then(|(near, _)| side_crossing(near)),
+            roots.and_then(|(_, far)| side_crossing(far)),
+            cap_crossing(0),
+            cap_crossing(1),
+        ]
+    }
+}
+
+impl ModelObject for Cylinder {
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let (distance, normal) = hit::nearest_hit(&self.crossings(ray))?;
+        let hit_point = ray.position + ray.direction * distance;
+        Some(Hit::new(ray, distance, normal, hit_point, self))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let base = Disk::new(self.base, self.axis, self.radius, self.material).bounding_box()?;
+        let top = Disk::new(self.top, self.axis, self.radius, self.material).bounding_box()?;
+        Some(base.union(&top))
+    }
+
+    fn is_closed(&self) -> bool {
+        // Only closed by the caps
+        self.caps.is_some()
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<Interval<'_>>> {
+        self.caps?;
+        let crossings = self.crossings(ray).iter().filter_map(|&crossing| crossing).collect();
+        Some(hit::intervals_between(self, crossings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::almost_eq;
+
+    #[test]
+    fn test_cylinder_hits() {
+        let cylinder = Cylinder::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 2.0, 0.0), 1.0, true, Material::default());
+        let hit = cylinder.try_hit(&Ray::new(Vector::new(-3.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 2.0));
+        assert!(hit.hit_normal.almost_equal_to(&Vector::new(-1.0, 0.0, 0.0), utils::EPSILON));
+
+        // Caps, and the inside of an open cylinder
+        let down = Ray::new(Vector::new(0.5, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        let hit = cylinder.try_hit(&down).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 3.0));
+        assert_eq!(hit.hit_normal, Vector::new(0.0, 1.0, 0.0));
+        let open = Cylinder::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 2.0, 0.0), 1.0, false, Material::default());
+        assert!(open.try_hit(&down).is_none());
+        let slanted = Ray::new(Vector::new(0.0, 2.5, 0.0), Vector::new(1.0, -1.0, 0.0).normalized());
+        let hit = open.try_hit(&slanted).expect("Expected a hit");
+        assert!(!hit.entering);
+        assert!(hit.hit_point.almost_equal_to(&Vector::new(1.0, 1.5, 0.0), utils::EPSILON));
+
+        // Past the ends
+        assert!(cylinder.try_hit(&Ray::new(Vector::new(-3.0, 2.1, 0.0), Vector::new(1.0, 0.0, 0.0))).is_none());
+    }
+}
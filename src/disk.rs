@@ -0,0 +1,69 @@
+use model_object::ModelObject;
+use vector::Vector;
+use ray::Ray;
+use hit::Hit;
+use material::Material;
+use bounding_box::BoundingBox;
+use std::option::Option::{None, Some};
+
+#[derive(Debug, Copy, Clone)]
+pub struct Disk {
+    pub material: Material,
+    pub center: Vector,
+    /// Unit length
+    pub normal: Vector,
+    pub radius: f64,
+}
+
+impl Disk {
+    pub fn new(center: Vector, normal: Vector, radius: f64, material: Material) -> Disk {
+        Disk {
+            material,
+            center,
+            normal: normal.normalized(),
+            radius,
+        }
+    }
+
+    /// Distance along the ray to where it crosses the disk
+    pub fn intersect(&self, ray: &Ray) -> Option<f64> {
+        self.intersect_line(ray).filter(|&distance| distance >= 0.0)
+    }
+
+    /// Like `intersect`, but also behind the start of the ray
+    pub fn intersect_line(&self, ray: &Ray) -> Option<f64> {
+        let cos_angle = self.normal % ray.direction;
+        if cos_angle == 0.0 {
+            return None;
+        }
+        let distance = ((self.center - ray.position) % self.normal) / cos_angle;
+        let hit_point = ray.position + ray.direction * distance;
+        if (hit_point - self.center).norm_squared() > self.radius * self.radius {
+            return None;
+        }
+        Some(distance)
+    }
+}
+
+impl ModelObject for Disk {
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let distance = self.intersect(ray)?;
+        let hit_point = ray.position + ray.direction * distance;
+        Some(Hit::new(ray, distance, self.normal, hit_point, self))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        // How far the rim reaches along each axis
+        let n = self.normal;
+        let extent = Vector::new(
+            self.radius * (1.0 - n.x * n.x).max(0.0).sqrt(),
+            self.radius * (1.0 - n.y * n.y).max(0.0).sqrt(),
+            self.radius * (1.0 - n.z * n.z).max(0.0).sqrt(),
+        );
+        Some(BoundingBox::new(self.center - extent, self.center + extent))
+    }
+}
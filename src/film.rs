@@ -0,0 +1,124 @@
+use color::{Color, BLACK};
+use color_image::ColorImage;
+use filter::Filter;
+
+/// Accumulates the filtered samples of a rectangle of the image's pixels.
+///
+/// Each sample is added to every pixel whose center is within the filter's radius, weighted by
+/// the filter. A pixel's color is the weighted average of the samples it got.
+pub struct Film {
+    filter: Filter,
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+    weighted_colors: Vec<Color>,
+    weights: Vec<f64>,
+}
+
+impl Film {
+    /// Film of a whole image
+    pub fn new(width: u32, height: u32, filter: Filter) -> Film {
+        Film::region(0, 0, width, height, filter)
+    }
+
+    /// Film of the given rectangle of an image. Samples are only added to the pixels inside it.
+    pub fn region(left: u32, top: u32, width: u32, height: u32, filter: Filter) -> Film {
+        let size = width as usize * height as usize;
+        Film {
+            filter,
+            left,
+            top,
+            width,
+            height,
+            weighted_colors: vec![BLACK; size],
+            weights: vec![0.0; size],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Adds a sample at the given point of the image
+    pub fn add_sample(&mut self, image_x: f64, image_y: f64, color: Color) {
+        let (first_x, last_x) = self.pixels_reached(image_x, self.left, self.width);
+        let (first_y, last_y) = self.pixels_reached(image_y, self.top, self.height);
+        for y in first_y..last_y {
+            for x in first_x..last_x {
+                let weight = self.filter.weight(x as f64 + 0.5 - image_x, y as f64 + 0.5 - image_y);
+                let index = (y - self.top) as usize * self.width as usize + (x - self.left) as usize;
+                self.weighted_colors[index] += color * weight;
+                self.weights[index] += weight;
+            }
+        }
+    }
+
+    /// Range of the pixels of one axis whose centers are within the filter's radius of the
+    /// coordinate, limited to the film. Centers exactly at the radius only count on the low side,
+    /// so that the box filter of radius 0.5 gives each sample to exactly one pixel.
+    fn pixels_reached(&self, coordinate: f64, start: u32, length: u32) -> (u32, u32) {
+        let first = (coordinate - 0.5 - self.filter.radius).floor() + 1.0;
+        let last = (coordinate - 0.5 + self.filter.radius).floor() + 1.0;
+        let clamp = |pixel: f64| pixel.max(start as f64).min((start + length) as f64) as u32;
+        (clamp(first), clamp(last))
+    }
+
+    /// Adds the samples of another film of the same image
+    pub fn merge(&mut self, other: &Film) {
+        let first_x = self.left.max(other.left);
+        let last_x = (self.left + self.width).min(other.left + other.width);
+        let first_y = self.top.max(other.top);
+        let last_y = (self.top + self.height).min(other.top + other.height);
+        for y in first_y..last_y {
+            for x in first_x..last_x {
+                let index = (y - self.top) as usize * self.width as usize + (x - self.left) as usize;
+                let other_index = (y - other.top) as usize * other.width as usize + (x - other.left) as usize;
+                self.weighted_colors[index] += other.weighted_colors[other_index];
+                self.weights[index] += other.weights[other_index];
+            }
+        }
+    }
+
+    /// Pixels without samples are black
+    pub fn to_color_image(&self) -> ColorImage {
+        let mut color_image = ColorImage::new(self.width, self.height);
+        for (pixel, (&weighted_color, &weight)) in color_image.pixels.iter_mut().zip(self.weighted_colors.iter().zip(self.weights.iter())) {
+            if weight > 0.0 {
+                *pixel = weighted_color / weight;
+            }
+        }
+        color_image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filter::FilterKind;
+
+    const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0 };
+
+    #[test]
+    fn test_splatting() {
+        // The box filter of radius 0.5 averages the samples inside each pixel
+        let mut film = Film::new(2, 1, Filter::default());
+        film.add_sample(0.0, 0.5, WHITE);
+        film.add_sample(0.9, 0.5, BLACK);
+        film.add_sample(1.0, 0.5, WHITE);
+        let color_image = film.to_color_image();
+        assert_eq!(color_image.pixels, vec![WHITE * 0.5, WHITE]);
+
+        // Wider filters spread samples to neighboring pixels, also across regions
+        let filter = Filter::new(FilterKind::Tent, 1.0);
+        let mut film = Film::new(3, 2, filter);
+        let mut region = Film::region(0, 1, 3, 1, filter);
+        film.add_sample(1.5, 0.75, WHITE);
+        region.add_sample(1.5, 1.25, BLACK);
+        film.merge(&region);
+        let color_image = film.to_color_image();
+        assert_eq!(color_image[(0, 0)], BLACK);
+        assert_eq!(color_image[(1, 0)], WHITE);
+        assert_eq!(color_image[(1, 1)], WHITE * 0.25);
+    }
+}
@@ -0,0 +1,132 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    /// Mitchell-Netravali, with B = C = 1/3
+    Mitchell,
+    /// Sinc windowed by a sinc as wide as the radius
+    Lanczos,
+}
+
+impl FromStr for FilterKind {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<FilterKind, ()> {
+        match name {
+            "box" => Ok(FilterKind::Box),
+            "tent" => Ok(FilterKind::Tent),
+            "gaussian" => Ok(FilterKind::Gaussian),
+            "mitchell" => Ok(FilterKind::Mitchell),
+            "lanczos" => Ok(FilterKind::Lanczos),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Weighs the samples around a pixel's center to get the pixel's color
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    /// In pixels, in each direction
+    pub radius: f64,
+}
+
+impl Default for Filter {
+    /// Averages the samples inside each pixel
+    fn default() -> Filter {
+        Filter::new(FilterKind::Box, 0.5)
+    }
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f64) -> Filter {
+        Filter { kind, radius }
+    }
+
+    pub fn with_default_radius(kind: FilterKind) -> Filter {
+        let radius = match kind {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        };
+        Filter::new(kind, radius)
+    }
+
+    /// Number of pixels on each side of a pixel that its samples can contribute to
+    pub fn pixel_reach(&self) -> u32 {
+        ((self.radius + 0.5).ceil() as u32).saturating_sub(1)
+    }
+
+    /// Weight of a sample at the given offset from a pixel's center
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, offset: f64) -> f64 {
+        let x = offset.abs();
+        if x > self.radius {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - x / self.radius,
+            FilterKind::Gaussian => {
+                // Shifted down so that it reaches 0 at the radius
+                let sigma = self.radius / 3.0;
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                gaussian(x) - gaussian(self.radius)
+            }
+            FilterKind::Mitchell => mitchell_netravali(2.0 * x / self.radius),
+            FilterKind::Lanczos => sinc(x) * sinc(x / self.radius),
+        }
+    }
+}
+
+fn mitchell_netravali(x: f64) -> f64 {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+    if x < 1.0 {
+        ((12.0 - 9.0 * B - 6.0 * C) * x * x * x + (-18.0 + 12.0 * B + 6.0 * C) * x * x + (6.0 - 2.0 * B)) / 6.0
+    } else if x < 2.0 {
+        ((-B - 6.0 * C) * x * x * x + (6.0 * B + 30.0 * C) * x * x + (-12.0 * B - 48.0 * C) * x + (8.0 * B + 24.0 * C)) / 6.0
+    } else {
+        0.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::almost_eq;
+
+    #[test]
+    fn test_weights() {
+        for &kind in [FilterKind::Box, FilterKind::Tent, FilterKind::Gaussian, FilterKind::Mitchell, FilterKind::Lanczos].iter() {
+            let filter = Filter::with_default_radius(kind);
+            assert!(filter.weight(0.0, 0.0) > 0.0);
+            assert_eq!(filter.weight(filter.radius + 0.01, 0.0), 0.0);
+            assert!(almost_eq(filter.weight(0.3, -0.2), filter.weight(-0.3, 0.2)));
+        }
+        assert!(almost_eq(Filter::with_default_radius(FilterKind::Tent).weight(0.5, 0.0), 0.5));
+        assert_eq!(Filter::default().pixel_reach(), 0);
+        assert_eq!(Filter::new(FilterKind::Gaussian, 1.5).pixel_reach(), 1);
+        assert_eq!(Filter::new(FilterKind::Gaussian, 1.6).pixel_reach(), 2);
+        // Negative lobes sharpen the image
+        assert!(Filter::with_default_radius(FilterKind::Mitchell).weight(1.5, 0.0) < 0.0);
+        assert!(Filter::with_default_radius(FilterKind::Lanczos).weight(1.5, 0.0) < 0.0);
+    }
+}
@@ -4,24 +4,96 @@ use ray::Ray;
 
 pub struct Hit<'a> {
     pub distance: f64,
+    /// Surface normal on the side the ray came from
     pub hit_normal: Vector,
     pub hit_point: Vector,
-    pub object: &'a ModelObject,
+    pub object: &'a dyn ModelObject,
     pub direction_to_source: Vector,
+    /// Whether the ray hit the outer side of the surface, i.e. it is entering the object if it is closed
+    pub entering: bool,
 }
 
 impl<'a> Hit<'a> {
+    /// `surface_normal` is the object's outward facing normal at the hit point
     pub fn new(hit_ray: &Ray,
                distance: f64,
-               hit_normal: Vector,
+               surface_normal: Vector,
                hit_point: Vector,
-               object: &'a ModelObject) -> Hit<'a> {
+               object: &'a dyn ModelObject) -> Hit<'a> {
+        let entering = surface_normal % hit_ray.direction <= 0.0;
         Hit {
-            distance: distance,
-            hit_normal: hit_normal,
-            hit_point: hit_point,
-            object: object,
+            distance,
+            hit_normal: if entering { surface_normal } else { -surface_normal },
+            hit_point,
+            object,
             direction_to_source: -hit_ray.direction,
+            entering,
         }
     }
-}
\ No newline at end of file
+
+    /// Replaces the normal used for shading, e.g. with an interpolated vertex normal.
+    /// `surface_normal` faces outwards, like the one given to `new`.
+    pub fn set_shading_normal(&mut self, surface_normal: Vector) {
+        self.hit_normal = if self.entering { surface_normal } else { -surface_normal };
+    }
+}
+
+/// The nearest of the candidate hits of an object in front of the ray, as (distance, outward normal)
+pub fn nearest_hit(candidates: &[Option<(f64, Vector)>]) -> Option<(f64, Vector)> {
+    candidates.iter()
+        .filter_map(|&candidate| candidate)
+        .filter(|&(distance, _)| distance >= 0.0)
+        .fold(None, |nearest: Option<(f64, Vector)>, candidate| match nearest {
+            Some(nearest) if nearest.0 <= candidate.0 => Some(nearest),
+            _ => Some(candidate),
+        })
+}
+
+/// Where the line of a ray crosses the surface of a closed object
+#[derive(Copy, Clone)]
+pub struct Crossing<'a> {
+    /// Along the ray, negative if behind its start
+    pub distance: f64,
+    /// Outward facing
+    pub normal: Vector,
+    pub object: &'a dyn ModelObject,
+}
+
+/// A part of the line of a ray that is inside an object
+#[derive(Copy, Clone)]
+pub struct Interval<'a> {
+    pub entry: Crossing<'a>,
+    pub exit: Crossing<'a>,
+}
+
+/// Pairs up where the line of a ray crosses the surface of a closed object, given as
+/// (distance, outward normal) in any order, into the intervals between entering and leaving it.
+/// An odd number of crossings, from a ray grazing the surface, can't be paired and counts as a miss.
+pub fn intervals_between(object: &dyn ModelObject, mut crossings: Vec<(f64, Vector)>) -> Vec<Interval<'_>> {
+    if !crossings.len().is_multiple_of(2) {
+        return Vec::new();
+    }
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let crossing = |(distance, normal): (f64, Vector)| Crossing { distance, normal, object };
+    crossings.chunks_exact(2)
+        .map(|pair| Interval { entry: crossing(pair[0]), exit: crossing(pair[1]) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sphere::Sphere;
+    use material::Material;
+
+    #[test]
+    fn test_intervals_between() {
+        let sphere = Sphere { material: Material::default(), center: Vector::new(0.0, 0.0, 0.0), radius: 1.0 };
+        let normal = Vector::new(1.0, 0.0, 0.0);
+        let intervals = intervals_between(&sphere, vec![(3.0, normal), (-1.0, normal), (2.0, normal), (1.0, normal)]);
+        let distances: Vec<(f64, f64)> = intervals.iter().map(|interval| (interval.entry.distance, interval.exit.distance)).collect();
+        assert_eq!(distances, vec![(-1.0, 1.0), (2.0, 3.0)]);
+
+        assert!(intervals_between(&sphere, vec![(1.0, normal), (2.0, normal), (3.0, normal)]).is_empty());
+    }
+}
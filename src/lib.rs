@@ -0,0 +1,40 @@
+#[macro_use]
+pub mod utils;
+pub mod vector;
+pub mod sphere;
+pub mod plane;
+pub mod triangle;
+pub mod cuboid;
+pub mod disk;
+pub mod cylinder;
+pub mod cone;
+pub mod torus;
+pub mod quad;
+pub mod csg;
+pub mod polynomial;
+pub mod mesh;
+pub mod obj_loader;
+pub mod transform;
+pub mod color;
+pub mod material;
+pub mod ray;
+pub mod hit;
+pub mod bounding_box;
+pub mod model_object;
+pub mod bvh;
+pub mod camera;
+pub mod sampler;
+pub mod filter;
+pub mod film;
+pub mod tile;
+pub mod color_image;
+pub mod scene;
+pub mod scene_parser;
+pub mod light;
+
+extern crate image;
+#[cfg(test)]
+extern crate rand;
+extern crate threadpool;
+#[cfg(test)]
+extern crate num_cpus;
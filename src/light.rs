@@ -1,12 +1,16 @@
 use color::Color;
 use vector::Vector;
+use quad::Quad;
 
 pub struct Light {
     pub position: Vector,
     pub color: Color,
     pub specular_intensity: f64,
     pub shadow_intensity: f64,
+    /// Width of the square, facing the lit point, that shadow rays are cast from
     pub radius: f64,
+    /// Casts shadow rays from this quad instead, for lights of a fixed shape and orientation
+    pub quad: Option<Quad>,
 }
 
 impl Light {
@@ -15,13 +19,24 @@ impl Light {
                specular_intensity: f64,
                shadow_intensity: f64,
                radius: f64) -> Light {
-        debug_assert!(0.0 <= shadow_intensity && shadow_intensity <= 1.0);
+        debug_assert!((0.0..=1.0).contains(&shadow_intensity));
         Light {
             position,
             color,
             specular_intensity,
             shadow_intensity,
-            radius
+            radius,
+            quad: None,
         }
     }
+
+    /// Light from a quad, which is centered on the quad
+    pub fn new_quad(quad: Quad,
+                    color: Color,
+                    specular_intensity: f64,
+                    shadow_intensity: f64) -> Light {
+        let mut light = Light::new(quad.center(), color, specular_intensity, shadow_intensity, 0.0);
+        light.quad = Some(quad);
+        light
+    }
 }
\ No newline at end of file
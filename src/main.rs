@@ -1,28 +1,149 @@
-#[macro_use]
-mod utils;
-mod vector;
-mod sphere;
-mod plane;
-mod triangle;
-mod color;
-mod material;
-mod ray;
-mod hit;
-mod model_object;
-mod camera;
-mod color_image;
-mod scene;
-mod light;
-
-extern crate image;
-extern crate rand;
-extern crate threadpool;
+extern crate rust_renderer;
 extern crate num_cpus;
 
+mod cli;
+
+use rust_renderer::scene::{Scene, AdaptiveSampling, RenderOptions, RenderProgress, RenderedImage};
+use rust_renderer::camera::Projection;
+use rust_renderer::color_image::ColorImage;
+use rust_renderer::filter::Filter;
+use rust_renderer::scene_parser::SceneParseError;
+use cli::{Command, Options, Verbosity};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process;
+use std::time::Instant;
+
+const EXIT_USAGE: i32 = 2;
+const EXIT_PARSE_ERROR: i32 = 3;
+const EXIT_IO_ERROR: i32 = 4;
+const EXIT_RENDER_ERROR: i32 = 5;
+
 fn main() {
-    let scene = scene::Scene::from_file_path("scenes/Pool.txt").unwrap(); //TODO
-    let color_image = scene.render(num_cpus::get());
-    let image_buffer = color_image.to_image_buffer();
-    image_buffer.save("output.png").unwrap();
-    println!("Done");
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, cli::USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    if let Err(exit_code) = run(&options) {
+        process::exit(exit_code);
+    }
+}
+
+fn run(options: &Options) -> Result<(), i32> {
+    let progress = |message: String| {
+        if options.verbosity == Verbosity::Progress {
+            println!("{}", message);
+        }
+    };
+
+    progress(format!("Loading scene {}", options.scene_path.display()));
+    let start = Instant::now();
+    let mut scene = Scene::from_file_path(&options.scene_path).map_err(|errors| {
+        for error in errors.iter() {
+            eprintln!("error: {}", error);
+        }
+        if errors.iter().any(|error| matches!(*error, SceneParseError::Io { .. } | SceneParseError::MeshIo { .. })) {
+            EXIT_IO_ERROR
+        } else {
+            EXIT_PARSE_ERROR
+        }
+    })?;
+    progress(format!("Loaded {} objects in {:.3} seconds", scene.objects.len(), seconds_since(start)));
+
+    if options.width.is_some() || options.height.is_some() {
+        // If only one dimension is given, keep the scene's aspect ratio
+        let aspect_ratio = scene.camera.image_width as f64 / scene.camera.image_height as f64;
+        let width = options.width.unwrap_or_else(|| (options.height.unwrap() as f64 * aspect_ratio).round().max(1.0) as u32);
+        let height = options.height.unwrap_or_else(|| (width as f64 / aspect_ratio).round().max(1.0) as u32);
+        scene.camera.set_resolution(width, height);
+        if scene.camera.projection == Projection::CubeMap && !scene.camera.has_cube_map_aspect() {
+            eprintln!("error: the cube map projection needs a width of 6 times the height (of each view, for stereo), got {}x{}", width, height);
+            return Err(EXIT_USAGE);
+        }
+    }
+    if let Some(tile_size) = options.tile_size {
+        scene.tile_size = tile_size;
+    }
+    if let Some(super_sampling_n) = options.super_sampling_n {
+        scene.set_super_sampling_n(super_sampling_n);
+    }
+    if let Some(shadow_rays_n) = options.shadow_rays_n {
+        scene.shadow_rays_n = shadow_rays_n;
+    }
+    if let Some(max_recursion) = options.max_recursion {
+        scene.max_recursion = max_recursion;
+    }
+    if let Some(seed) = options.seed {
+        scene.seed = seed;
+    }
+    if let Some(sampler) = options.sampler {
+        scene.sampler = sampler;
+    }
+    if let Some(kind) = options.filter {
+        scene.filter = Filter::with_default_radius(kind);
+    }
+    if let Some(radius) = options.filter_radius {
+        scene.filter.radius = radius;
+    }
+    if let Some(noise_threshold) = options.noise_threshold {
+        let min_samples = scene.adaptive_sampling.map_or(4, |adaptive| adaptive.min_samples);
+        scene.adaptive_sampling = Some(AdaptiveSampling { min_samples, noise_threshold });
+    }
+
+    let thread_count = options.thread_count.unwrap_or_else(num_cpus::get);
+    progress(format!("Rendering {}x{} pixels on {} threads",
+                     scene.camera.image_width, scene.camera.image_height, thread_count));
+    let start = Instant::now();
+    let (width, height) = (scene.camera.image_width, scene.camera.image_height);
+    let max_samples = scene.camera.samples_per_pixel();
+    let mut render_options = RenderOptions::new(thread_count);
+    // Redraw the bar in place, only on a terminal so that logs don't fill up with it
+    let show_progress_bar = options.verbosity != Verbosity::Quiet && io::stderr().is_terminal();
+    if show_progress_bar {
+        render_options.progress = Some(Box::new(|render_progress: &RenderProgress| {
+            eprint!("\r{}", cli::progress_bar(render_progress));
+        }));
+    }
+    let rendered_image = scene.render_with_options(render_options);
+    if show_progress_bar {
+        eprintln!();
+    }
+    let RenderedImage { color_image, sample_counts, .. } = rendered_image.map_err(|error| {
+        eprintln!("error: rendering failed: {}", error);
+        EXIT_RENDER_ERROR
+    })?;
+    let total_samples: u64 = sample_counts.iter().map(|&count| u64::from(count)).sum();
+    progress(format!("Rendered in {:.3} seconds, {:.2} samples per pixel on average",
+                     seconds_since(start), total_samples as f64 / sample_counts.len() as f64));
+
+    save_image(&color_image, &options.output_path, options.verbosity)?;
+    if let Some(ref heatmap_path) = options.heatmap_path {
+        let heatmap = ColorImage::heatmap(&sample_counts, width, height, max_samples);
+        save_image(&heatmap, heatmap_path, options.verbosity)?;
+    }
+    Ok(())
+}
+
+fn save_image(color_image: &ColorImage, path: &Path, verbosity: Verbosity) -> Result<(), i32> {
+    color_image.to_image_buffer().save(path).map_err(|error| {
+        eprintln!("error: could not write {}: {}", path.display(), error);
+        EXIT_IO_ERROR
+    })?;
+    if verbosity != Verbosity::Quiet {
+        println!("Saved {}", path.display());
+    }
+    Ok(())
+}
+
+fn seconds_since(start: Instant) -> f64 {
+    let duration = start.elapsed();
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
 }
@@ -7,6 +7,7 @@ pub struct Material {
     pub reflection_color: Color,
     pub phong_specularity: f64,
     pub transparency: f64,
+    pub refractive_index: f64,
     _is_transparent: bool,
     _is_reflective: bool,
     _is_specular: bool,
@@ -17,14 +18,17 @@ impl Material {
                specular_color: Color,
                reflection_color: Color,
                phong_specularity: f64,
-               transparency: f64) -> Material {
-        debug_assert!(0.0 <= transparency && transparency <= 1.0);
+               transparency: f64,
+               refractive_index: f64) -> Material {
+        debug_assert!((0.0..=1.0).contains(&transparency));
+        debug_assert!(refractive_index > 0.0);
         Material {
-            diffuse_color: diffuse_color,
-            specular_color: specular_color,
-            reflection_color: reflection_color,
-            phong_specularity: phong_specularity,
-            transparency: transparency,
+            diffuse_color,
+            specular_color,
+            reflection_color,
+            phong_specularity,
+            transparency,
+            refractive_index,
             _is_transparent : transparency > 0.0,
             _is_reflective: reflection_color != BLACK,
             _is_specular: specular_color != BLACK,
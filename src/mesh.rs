@@ -0,0 +1,99 @@
+use model_object::ModelObject;
+use vector::Vector;
+use ray::Ray;
+use hit::Hit;
+use material::Material;
+use bounding_box::BoundingBox;
+use transform::Transform;
+use triangle::{intersect_triangle, orient_face_normal};
+use std::sync::Arc;
+
+/// Corner of a mesh face, as indexes into the mesh's vertex attribute arrays
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeshVertex {
+    pub position: usize,
+    pub texture_coordinate: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+/// Triangle mesh whose faces share vertex storage and material
+#[derive(Debug)]
+pub struct Mesh {
+    pub material: Material,
+    pub positions: Vec<Vector>,
+    pub texture_coordinates: Vec<(f64, f64)>,
+    pub normals: Vec<Vector>,
+    pub faces: Vec<[MeshVertex; 3]>,
+}
+
+impl Mesh {
+    pub fn transform(&mut self, transform: &Transform) {
+        for position in self.positions.iter_mut() {
+            *position = transform.apply_to_point(position);
+        }
+        for normal in self.normals.iter_mut() {
+            *normal = transform.apply_to_direction(normal);
+        }
+    }
+
+    /// Creates one object per (non degenerate) face, all referencing this mesh
+    pub fn into_objects(self) -> Vec<Box<dyn ModelObject + Send + Sync>> {
+        let mesh = Arc::new(self);
+        let mut objects: Vec<Box<dyn ModelObject + Send + Sync>> = Vec::new();
+        for face in 0..mesh.faces.len() {
+            let [v1, v2, v3] = mesh.face_positions(face);
+            let normal = (v2 - v1) ^ (v3 - v1);
+            if normal.norm_squared() == 0.0 {
+                continue;
+            }
+            let mut normal = normal.normalized();
+            if let Some(ref vertex_normals) = mesh.face_normals(face) {
+                normal = orient_face_normal(normal, vertex_normals);
+            }
+            objects.push(Box::new(MeshTriangle {
+                mesh: mesh.clone(),
+                face,
+                normal,
+            }));
+        }
+        objects
+    }
+
+    pub fn face_positions(&self, face: usize) -> [Vector; 3] {
+        let [a, b, c] = self.faces[face];
+        [self.positions[a.position], self.positions[b.position], self.positions[c.position]]
+    }
+
+    /// Vertex normals of a face, if all of its vertices have one
+    pub fn face_normals(&self, face: usize) -> Option<[Vector; 3]> {
+        let [a, b, c] = self.faces[face];
+        Some([self.normals[a.normal?], self.normals[b.normal?], self.normals[c.normal?]])
+    }
+}
+
+pub struct MeshTriangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+    normal: Vector,
+}
+
+impl ModelObject for MeshTriangle {
+    fn material(&self) -> Material {
+        self.mesh.material
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let [v1, v2, v3] = self.mesh.face_positions(self.face);
+        let intersection = intersect_triangle(ray, v1, v2, v3)?;
+        let hit_point = ray.position + ray.direction * intersection.distance;
+        let mut hit = Hit::new(ray, intersection.distance, self.normal, hit_point, self);
+        if let Some(normal) = self.mesh.face_normals(self.face).and_then(|ref vertex_normals| intersection.interpolate_normal(vertex_normals)) {
+            hit.set_shading_normal(normal);
+        }
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::from_points(self.mesh.face_positions(self.face).iter().cloned()))
+    }
+}
@@ -1,9 +1,21 @@
 use ray::Ray;
-use hit::Hit;
+use hit::{Hit, Interval};
 use std::option::Option;
 use material::Material;
+use bounding_box::BoundingBox;
 
 pub trait ModelObject {
     fn material(&self) -> Material;
-    fn try_hit(&self, ray: &Ray) -> Option<Hit>;
-}
\ No newline at end of file
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>>;
+    /// Axis aligned bounds of the object, or None if it is unbounded
+    fn bounding_box(&self) -> Option<BoundingBox>;
+    /// Whether the object encloses a volume, in which case intervals returns Some
+    fn is_closed(&self) -> bool {
+        false
+    }
+    /// The parts of the ray's whole line, including behind its start, that are inside the object,
+    /// in increasing order. None if the object doesn't enclose a volume, whatever the ray.
+    fn intervals(&self, _ray: &Ray) -> Option<Vec<Interval<'_>>> {
+        None
+    }
+}
@@ -0,0 +1,181 @@
+use mesh::{Mesh, MeshVertex};
+use material::Material;
+use vector::Vector;
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ObjError::Io(ref error) => write!(f, "{}", error),
+            ObjError::Parse { line, ref reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl error::Error for ObjError {}
+
+pub fn load_obj<P: AsRef<Path>>(file_path: P, material: Material) -> Result<Mesh, ObjError> {
+    let file = File::open(file_path).map_err(ObjError::Io)?;
+    parse_obj(BufReader::new(file), material)
+}
+
+/// Parses Wavefront OBJ geometry: vertex positions, texture coordinates, normals and faces.
+/// Polygons are fan triangulated. Other statements (groups, materials, ...) and vertex colors are ignored.
+/// Zero length normals are kept so indexes stay valid, but faces using them get their flat face normal.
+pub fn parse_obj<R: BufRead>(reader: R, material: Material) -> Result<Mesh, ObjError> {
+    let mut mesh = Mesh {
+        material,
+        positions: Vec::new(),
+        texture_coordinates: Vec::new(),
+        normals: Vec::new(),
+        faces: Vec::new(),
+    };
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line.map_err(ObjError::Io)?;
+        let line_number = line_index + 1;
+        let error = |reason: String| ObjError::Parse { line: line_number, reason };
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("v") => {
+                // x y z, optionally followed by w or by r g b
+                let values = parse_numbers(&mut parts, 3, 6).map_err(error)?;
+                mesh.positions.push(Vector::new(values[0], values[1], values[2]));
+            }
+            Some("vt") => {
+                let values = parse_numbers(&mut parts, 1, 3).map_err(error)?;
+                mesh.texture_coordinates.push((values[0], values.get(1).cloned().unwrap_or(0.0)));
+            }
+            Some("vn") => {
+                let values = parse_numbers(&mut parts, 3, 3).map_err(error)?;
+                let normal = Vector::new(values[0], values[1], values[2]);
+                mesh.normals.push(if normal.norm_squared() == 0.0 { normal } else { normal.normalized() });
+            }
+            Some("f") => {
+                let vertices = parts
+                    .map(|part| parse_face_vertex(part, &mesh))
+                    .collect::<Result<Vec<MeshVertex>, String>>()
+                    .map_err(error)?;
+                if vertices.len() < 3 {
+                    return Err(error(format!("face has {} vertices, at least 3 are required", vertices.len())));
+                }
+                for i in 1..vertices.len() - 1 {
+                    mesh.faces.push([vertices[0], vertices[i], vertices[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+fn parse_numbers<'a, I: Iterator<Item=&'a str>>(parts: &mut I, min_count: usize, max_count: usize) -> Result<Vec<f64>, String> {
+    let values = parts
+        .map(|part| part.parse::<f64>().map_err(|_| format!("invalid number '{}'", part)))
+        .collect::<Result<Vec<f64>, String>>()?;
+    if values.len() < min_count || values.len() > max_count {
+        return Err(format!("expected {} to {} numbers, found {}", min_count, max_count, values.len()));
+    }
+    Ok(values)
+}
+
+/// Parses "v", "v/vt", "v//vn" or "v/vt/vn"
+fn parse_face_vertex(part: &str, mesh: &Mesh) -> Result<MeshVertex, String> {
+    let mut indexes = part.split('/');
+    let position = indexes.next().unwrap_or("");
+    let texture_coordinate = indexes.next().unwrap_or("");
+    let normal = indexes.next().unwrap_or("");
+    if indexes.next().is_some() {
+        return Err(format!("invalid face vertex '{}'", part));
+    }
+    Ok(MeshVertex {
+        position: resolve_index(position, mesh.positions.len())?,
+        texture_coordinate: optional_index(texture_coordinate, mesh.texture_coordinates.len())?,
+        normal: optional_index(normal, mesh.normals.len())?
+            .filter(|&index| mesh.normals[index].norm_squared() != 0.0),
+    })
+}
+
+fn optional_index(index: &str, count: usize) -> Result<Option<usize>, String> {
+    if index.is_empty() {
+        Ok(None)
+    } else {
+        resolve_index(index, count).map(Some)
+    }
+}
+
+/// Converts a 1-based (or negative, relative to the end) OBJ index into a 0-based one
+fn resolve_index(index: &str, count: usize) -> Result<usize, String> {
+    let value: i64 = index.parse().map_err(|_| format!("invalid index '{}'", index))?;
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {} is out of range ({} defined)", value, count));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_obj() {
+        let text = "\
+# unit square as a quad, then a triangle using relative indexes
+o square
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 2
+f 1//1 2//1 3//1 4//1
+v 2 0 0
+f -1/1 -4/2 -3/-1
+";
+        let mesh = parse_obj(text.as_bytes(), Material::default()).expect("Could not parse OBJ");
+        assert_eq!(mesh.positions.len(), 5);
+        assert_eq!(mesh.normals, vec![Vector::new(0.0, 0.0, 1.0)]);
+        let positions: Vec<[usize; 3]> = mesh.faces.iter()
+            .map(|face| [face[0].position, face[1].position, face[2].position])
+            .collect();
+        assert_eq!(positions, vec![[0, 1, 2], [0, 2, 3], [4, 1, 2]]);
+        assert_eq!(mesh.faces[0][0].normal, Some(0));
+        assert_eq!(mesh.faces[2][2].texture_coordinate, Some(2));
+        assert_eq!(mesh.faces[2][2].normal, None);
+
+        // Vertex colors are ignored and vertices with a zero length normal fall back to the face normal
+        let text = "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0 0 1\nvn 0 0 1\nvn 0 0 0\nf 1//1 2//2 3//1\n";
+        let mesh = parse_obj(text.as_bytes(), Material::default()).expect("Could not parse OBJ");
+        assert_eq!(mesh.positions[1], Vector::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.faces[0][0].normal, Some(0));
+        assert_eq!(mesh.faces[0][1].normal, None);
+        assert!(mesh.face_normals(0).is_none());
+
+        match parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n".as_bytes(), Material::default()) {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 3),
+            _ => panic!("Expected a parse error"),
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use model_object::ModelObject;
 use vector::Vector;
 use ray::Ray;
-use hit::Hit;
+use hit::{Hit, Crossing, Interval};
 use material::Material;
+use bounding_box::BoundingBox;
+use std::f64;
 use std::option::Option::{None, Some};
 
 pub struct Plane {
@@ -16,7 +18,7 @@ impl ModelObject for Plane {
         self.material
     }
 
-    fn try_hit(&self, ray: &Ray) -> Option<Hit> {
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
         let cos_angle = self.normal % ray.direction;
         if cos_angle == 0.0 {
             return None;
@@ -26,11 +28,33 @@ impl ModelObject for Plane {
             return None;
         }
         let hit_point = ray.position + (t * ray.direction);
-        let hit_normal = if cos_angle > 0.0 {
-            -self.normal
+        Some(Hit::new(ray, t, self.normal, hit_point, self))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        None
+    }
+
+    /// A plane is the surface of the half space behind it, so a transparent plane refracts like
+    /// the surface of a solid (e.g. water), instead of being a thin sheet like a triangle or quad
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<Interval<'_>>> {
+        // The plane encloses the half space behind its normal
+        let cos_angle = self.normal % ray.direction;
+        let height = (ray.position % self.normal) - self.offset;
+        let (entry, exit) = if cos_angle == 0.0 {
+            if height > 0.0 {
+                return Some(Vec::new());
+            }
+            (f64::NEG_INFINITY, f64::INFINITY)
         } else {
-            self.normal
+            let t = -height / cos_angle;
+            if cos_angle > 0.0 { (f64::NEG_INFINITY, t) } else { (t, f64::INFINITY) }
         };
-        Some(Hit::new(ray, t, hit_normal, hit_point, self))
+        let crossing = |distance| Crossing { distance, normal: self.normal, object: self };
+        Some(vec![Interval { entry: crossing(entry), exit: crossing(exit) }])
     }
 }
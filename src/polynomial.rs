@@ -0,0 +1,94 @@
+/// Polynomials are given by their coefficients, lowest degree first
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |value, &coefficient| value * x + coefficient)
+}
+
+/// Real roots of the polynomial within [min, max], in increasing order.
+///
+/// The polynomial is monotonic between consecutive roots of its derivative (found recursively),
+/// so each of those intervals has at most one root, which is found by bisection. This is slower
+/// than closed form solutions for cubics and quartics, but doesn't lose precision to cancellation.
+/// Roots where the polynomial touches 0 without changing sign may be missed.
+pub fn real_roots(coefficients: &[f64], min: f64, max: f64) -> Vec<f64> {
+    let degree = match coefficients.iter().rposition(|&coefficient| coefficient != 0.0) {
+        Some(degree) => degree,
+        None => return Vec::new(),
+    };
+    let coefficients = &coefficients[..=degree];
+    if degree == 0 {
+        return Vec::new();
+    }
+    if degree == 1 {
+        let root = -coefficients[0] / coefficients[1];
+        return if root >= min && root <= max { vec![root] } else { Vec::new() };
+    }
+
+    let derivative: Vec<f64> = coefficients.iter().enumerate().skip(1)
+        .map(|(power, &coefficient)| coefficient * power as f64)
+        .collect();
+    let mut bounds = vec![min];
+    bounds.extend(real_roots(&derivative, min, max));
+    bounds.push(max);
+    let mut roots: Vec<f64> = bounds.windows(2)
+        .filter_map(|interval| bisect(coefficients, interval[0], interval[1]))
+        .collect();
+    // A root on the bound between two intervals is found in both
+    roots.dedup();
+    roots
+}
+
+/// The root within [low, high] if the polynomial changes sign there
+fn bisect(coefficients: &[f64], mut low: f64, mut high: f64) -> Option<f64> {
+    let low_value = evaluate(coefficients, low);
+    let high_value = evaluate(coefficients, high);
+    if low_value == 0.0 {
+        return Some(low);
+    }
+    if high_value == 0.0 {
+        return Some(high);
+    }
+    if low_value.signum() == high_value.signum() {
+        return None;
+    }
+    loop {
+        let middle = 0.5 * (low + high);
+        // Stop once the interval can't be split any further
+        if middle <= low || middle >= high {
+            return Some(middle);
+        }
+        let value = evaluate(coefficients, middle);
+        if value == 0.0 {
+            return Some(middle);
+        }
+        if value.signum() == low_value.signum() {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::almost_eq;
+
+    #[test]
+    fn test_real_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        let roots = real_roots(&[24.0, -50.0, 35.0, -10.0, 1.0], -10.0, 10.0);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0].iter()) {
+            assert!(almost_eq(*root, *expected));
+        }
+        // Only within the bounds
+        assert_eq!(real_roots(&[24.0, -50.0, 35.0, -10.0, 1.0], 1.5, 2.5).len(), 1);
+        // x^4 + 1 has no real roots, and a zero leading coefficient lowers the degree
+        assert!(real_roots(&[1.0, 0.0, 0.0, 0.0, 1.0], -10.0, 10.0).is_empty());
+        let roots = real_roots(&[-4.0, 0.0, 1.0, 0.0, 0.0], -10.0, 10.0);
+        assert!(roots.len() == 2 && almost_eq(roots[0], -2.0) && almost_eq(roots[1], 2.0));
+        // Close roots, (x - 1)(x - 1.001)(x + 5)^2
+        let roots = real_roots(&[25.025, -40.015, 5.991, 7.999, 1.0], 0.0, 10.0);
+        assert!(roots.len() == 2 && (roots[0] - 1.0).abs() < 1e-9 && (roots[1] - 1.001).abs() < 1e-9);
+    }
+}
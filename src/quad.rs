@@ -0,0 +1,101 @@
+use model_object::ModelObject;
+use vector::Vector;
+use ray::Ray;
+use hit::Hit;
+use material::Material;
+use bounding_box::BoundingBox;
+use std::option::Option::{None, Some};
+
+/// Parallelogram spanned by two edges from a corner
+#[derive(Debug, Copy, Clone)]
+pub struct Quad {
+    pub material: Material,
+    pub corner: Vector,
+    pub edge1: Vector,
+    pub edge2: Vector,
+    /// Unit length, facing the side from which edge2 is counterclockwise from edge1
+    pub normal: Vector,
+    /// edge1 ^ edge2 divided by its squared length, to get the edge coordinates of a point
+    scaled_normal: Vector,
+}
+
+impl Quad {
+    pub fn new(corner: Vector, edge1: Vector, edge2: Vector, material: Material) -> Quad {
+        let cross = edge1 ^ edge2;
+        Quad {
+            material,
+            corner,
+            edge1,
+            edge2,
+            normal: cross.normalized(),
+            scaled_normal: cross * (1.0 / cross.norm_squared()),
+        }
+    }
+
+    /// The point at the given fractions of the edges
+    pub fn point_at(&self, u: f64, v: f64) -> Vector {
+        self.corner + self.edge1 * u + self.edge2 * v
+    }
+
+    pub fn center(&self) -> Vector {
+        self.point_at(0.5, 0.5)
+    }
+}
+
+impl ModelObject for Quad {
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let cos_angle = self.normal % ray.direction;
+        if cos_angle == 0.0 {
+            return None;
+        }
+        let distance = ((self.corner - ray.position) % self.normal) / cos_angle;
+        if distance < 0.0 {
+            return None;
+        }
+        let hit_point = ray.position + ray.direction * distance;
+        // Fractions of the edges that lead to the hit point
+        let offset = hit_point - self.corner;
+        let u = self.scaled_normal % (offset ^ self.edge2);
+        let v = self.scaled_normal % (self.edge1 ^ offset);
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+        Some(Hit::new(ray, distance, self.normal, hit_point, self))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::from_points(vec![
+            self.corner,
+            self.corner + self.edge1,
+            self.corner + self.edge2,
+            self.corner + self.edge1 + self.edge2,
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::{self, almost_eq};
+
+    #[test]
+    fn test_quad_hits() {
+        // A slanted parallelogram in the z = 2 plane
+        let quad = Quad::new(Vector::new(0.0, 0.0, 2.0), Vector::new(2.0, 0.0, 0.0), Vector::new(1.0, 1.0, 0.0), Material::default());
+        let forward = Vector::new(0.0, 0.0, 1.0);
+        let hit = quad.try_hit(&Ray::new(Vector::new(1.5, 0.5, 0.0), forward)).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 2.0));
+        // Seen from behind its normal, which is along edge1 x edge2
+        assert_eq!(hit.hit_normal, Vector::new(0.0, 0.0, -1.0));
+        assert!(!hit.entering);
+        // Inside the bounding rectangle, but outside the slanted edges
+        assert!(quad.try_hit(&Ray::new(Vector::new(0.2, 0.9, 0.0), forward)).is_none());
+        assert!(quad.try_hit(&Ray::new(Vector::new(2.8, 0.1, 0.0), forward)).is_none());
+        assert!(quad.try_hit(&Ray::new(Vector::new(2.8, 0.9, 0.0), forward)).is_some());
+        assert!(quad.center().almost_equal_to(&Vector::new(1.5, 0.5, 2.0), utils::EPSILON));
+    }
+}
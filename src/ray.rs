@@ -8,7 +8,7 @@ pub struct Ray {
 impl Ray {
     pub fn new(position: Vector, direction: Vector) -> Ray {
         debug_assert!(::utils::almost_eq(direction.norm(), 1.0));
-        Ray { position: position, direction: direction }
+        Ray { position, direction }
     }
 
     pub fn construct_ray(from: Vector, to: Vector) -> Ray {
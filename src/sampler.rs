@@ -0,0 +1,344 @@
+use std::str::FromStr;
+
+/// Source of sample points in the unit square for one pixel.
+///
+/// A pixel is rendered with a number of pixel samples (camera rays), and each of them can use
+/// nested sets of points, e.g. for the rays to an area light. The points of a nested set are
+/// spread over all of the pixel's samples, so together they are well distributed too.
+/// Different dimensions are independent of each other.
+pub trait Sampler {
+    /// Starts pixel sample `index` of `count`
+    fn start_pixel_sample(&mut self, index: u32, count: u32);
+
+    /// Point `index` of a set of `count` points, for the current pixel sample
+    fn sample_2d(&mut self, dimension: u32, index: u32, count: u32) -> (f64, f64);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SamplerKind {
+    /// Uniform random points
+    Independent,
+    /// One random point in each cell of a grid (or of each row and column if the count isn't a square)
+    Stratified,
+    /// Halton sequence, shuffled per dimension and randomly shifted per pixel
+    Halton,
+    /// Sobol sequence with Owen scrambling
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Creates the sampler of one pixel. Samplers with the same seed return the same points.
+    pub fn create(&self, seed: u64) -> Box<dyn Sampler> {
+        let sample = PixelSample { seed, index: 0, count: 1, dimensions: Vec::new() };
+        match *self {
+            SamplerKind::Independent => Box::new(IndependentSampler { sample }),
+            SamplerKind::Stratified => Box::new(StratifiedSampler { sample }),
+            SamplerKind::Halton => Box::new(HaltonSampler { sample }),
+            SamplerKind::Sobol => Box::new(SobolSampler { sample }),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<SamplerKind, ()> {
+        match name {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Seed of a pixel's sampler. It only depends on the render's seed and the pixel's coordinates,
+/// so renders don't depend on which thread renders which pixel.
+pub fn pixel_seed(seed: u64, x: u32, y: u32) -> u64 {
+    split_mix(seed ^ split_mix((u64::from(x) << 32) | u64::from(y)))
+}
+
+/// The pixel's seed and current pixel sample, shared by all samplers
+struct PixelSample {
+    seed: u64,
+    index: u32,
+    count: u32,
+    /// The dimensions used so far, so that each is only set up once per pixel
+    dimensions: Vec<Dimension>,
+}
+
+struct Dimension {
+    /// Hashed with the index of a point to get its random values
+    seed: u64,
+    /// Seeds of the scrambles of the dimension's points
+    scrambles: [u32; 3],
+    /// Random orders of the dimension's points, per axis
+    shuffles: [Shuffle; 2],
+}
+
+impl PixelSample {
+    /// Index and count of a point of a nested set among the sets of all of the pixel's samples
+    fn global(&self, index: u32, count: u32) -> (u32, u32) {
+        (self.index * count + index, self.count * count)
+    }
+
+    fn dimension(&mut self, dimension: u32) -> &mut Dimension {
+        while self.dimensions.len() <= dimension as usize {
+            let seed = split_mix(self.seed ^ split_mix(self.dimensions.len() as u64));
+            // Point indices are below 2^32, so these don't collide with the points' hashes
+            let low = split_mix(seed ^ (1 << 63));
+            let high = split_mix(seed ^ (1 << 62));
+            self.dimensions.push(Dimension {
+                seed,
+                scrambles: [low as u32, (low >> 32) as u32, high as u32],
+                shuffles: [Shuffle::new(split_mix(seed ^ (1 << 61))), Shuffle::new(split_mix(seed ^ (1 << 60)))],
+            });
+        }
+        &mut self.dimensions[dimension as usize]
+    }
+
+    /// Two random values in [0, 1), with 32 bits each, fixed for the pixel and the given values
+    fn random_2d(&mut self, dimension: u32, index: u32) -> (f64, f64) {
+        let hash = split_mix(self.dimension(dimension).seed ^ u64::from(index));
+        let scale = 1.0 / (1u64 << 32) as f64;
+        ((hash >> 32) as f64 * scale, (hash as u32) as f64 * scale)
+    }
+
+    /// Random value fixed for the pixel and the dimension, `axis` is below 3
+    fn random_u32(&mut self, dimension: u32, axis: usize) -> u32 {
+        self.dimension(dimension).scrambles[axis]
+    }
+
+    /// The element at `index` of a random order of [0, count), fixed for the pixel, the dimension
+    /// and the axis (0 or 1)
+    fn shuffled(&mut self, dimension: u32, axis: usize, index: u32, count: u32) -> u32 {
+        self.dimension(dimension).shuffles[axis].get(index, count)
+    }
+}
+
+/// Random permutation of [0, count), shuffled only as far as it is used (Fisher-Yates shuffle).
+/// This is much cheaper than hashing each index into a permutation, and only costs as much
+/// as the number of points used, which is often a small part of the count for nested sets of
+/// later dimensions.
+struct Shuffle {
+    seed: u64,
+    order: Vec<u32>,
+    /// The elements before this are final
+    shuffled: u32,
+}
+
+impl Shuffle {
+    fn new(seed: u64) -> Shuffle {
+        Shuffle { seed, order: Vec::new(), shuffled: 0 }
+    }
+
+    fn get(&mut self, index: u32, count: u32) -> u32 {
+        if self.order.len() != count as usize {
+            self.order = (0..count).collect();
+            self.shuffled = 0;
+        }
+        while self.shuffled <= index {
+            // Swaps in a random one of the remaining elements, scaling a random 32 bit value
+            let i = self.shuffled;
+            let random = split_mix(self.seed.wrapping_add(u64::from(i))) >> 32;
+            let j = i + ((random * u64::from(count - i)) >> 32) as u32;
+            self.order.swap(i as usize, j as usize);
+            self.shuffled += 1;
+        }
+        self.order[index as usize]
+    }
+}
+
+struct IndependentSampler {
+    sample: PixelSample,
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, index: u32, count: u32) {
+        self.sample.index = index;
+        self.sample.count = count;
+    }
+
+    fn sample_2d(&mut self, dimension: u32, index: u32, count: u32) -> (f64, f64) {
+        let (index, _) = self.sample.global(index, count);
+        self.sample.random_2d(dimension, index)
+    }
+}
+
+struct StratifiedSampler {
+    sample: PixelSample,
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, index: u32, count: u32) {
+        self.sample.index = index;
+        self.sample.count = count;
+    }
+
+    fn sample_2d(&mut self, dimension: u32, index: u32, count: u32) -> (f64, f64) {
+        let (index, count) = self.sample.global(index, count);
+        let (jitter_x, jitter_y) = self.sample.random_2d(dimension, index);
+        // Shuffle the cells, so that different dimensions don't use the same cell order
+        let side = (count as f64).sqrt().round() as u32;
+        if side * side == count {
+            let cell = self.sample.shuffled(dimension, 0, index, count);
+            (((cell % side) as f64 + jitter_x) / side as f64,
+             ((cell / side) as f64 + jitter_y) / side as f64)
+        } else {
+            let column = self.sample.shuffled(dimension, 0, index, count);
+            let row = self.sample.shuffled(dimension, 1, index, count);
+            ((column as f64 + jitter_x) / count as f64, (row as f64 + jitter_y) / count as f64)
+        }
+    }
+}
+
+struct HaltonSampler {
+    sample: PixelSample,
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, index: u32, count: u32) {
+        self.sample.index = index;
+        self.sample.count = count;
+    }
+
+    /// Uses bases 2 and 3 for every dimension, shuffling the points of each dimension
+    /// independently. Higher bases need many points before they stop being correlated.
+    fn sample_2d(&mut self, dimension: u32, index: u32, count: u32) -> (f64, f64) {
+        let (index, count) = self.sample.global(index, count);
+        let index = self.sample.shuffled(dimension, 0, index, count);
+        // Cranley-Patterson rotation, so that pixels don't all get the same points
+        let (shift_x, shift_y) = self.sample.random_2d(dimension, 0);
+        ((radical_inverse(2, index) + shift_x).fract(), (radical_inverse(3, index) + shift_y).fract())
+    }
+}
+
+/// Mirrors the digits of `index` in `base` around the radix point
+fn radical_inverse(base: u32, mut index: u32) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut digit_weight = inverse_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * digit_weight;
+        index /= base;
+        digit_weight *= inverse_base;
+    }
+    result
+}
+
+struct SobolSampler {
+    sample: PixelSample,
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, index: u32, count: u32) {
+        self.sample.index = index;
+        self.sample.count = count;
+    }
+
+    /// Uses the first two Sobol dimensions for every dimension, with an independent shuffling
+    /// and Owen scrambling each time (Burley, "Practical Hash-based Owen Scrambling")
+    fn sample_2d(&mut self, dimension: u32, index: u32, count: u32) -> (f64, f64) {
+        let (index, _) = self.sample.global(index, count);
+        let index = nested_uniform_scramble(index, self.sample.random_u32(dimension, 0));
+        let x = nested_uniform_scramble(index.reverse_bits(), self.sample.random_u32(dimension, 1));
+        let y = nested_uniform_scramble(sobol_second_dimension(index), self.sample.random_u32(dimension, 2));
+        let scale = 1.0 / (1u64 << 32) as f64;
+        (x as f64 * scale, y as f64 * scale)
+    }
+}
+
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+/// Owen scrambling: randomly flips each bit, depending on the bits above it
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    laine_karras_permutation(value.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash in which each bit only depends on the bits below it
+fn laine_karras_permutation(mut value: u32, seed: u32) -> u32 {
+    value = value.wrapping_add(seed);
+    value ^= value.wrapping_mul(0x6c50_b47c);
+    value ^= value.wrapping_mul(0xb82f_1e52);
+    value ^= value.wrapping_mul(0xc7af_e638);
+    value ^= value.wrapping_mul(0x8d22_f6e6);
+    value
+}
+
+/// SplitMix64 finalizer, scrambles the bits of its input
+fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that each of the count x count cells of the unit square gets exactly one point,
+    /// when the points of all pixel samples are taken together
+    fn assert_stratified(kind: SamplerKind, pixel_sample_count: u32, nested_count: u32) {
+        let mut sampler = kind.create(pixel_seed(5, 3, 4));
+        let count = pixel_sample_count * nested_count;
+        let side = (count as f64).sqrt() as usize;
+        let mut cells = vec![0; count as usize];
+        for pixel_sample in 0..pixel_sample_count {
+            sampler.start_pixel_sample(pixel_sample, pixel_sample_count);
+            for index in 0..nested_count {
+                let (x, y) = sampler.sample_2d(3, index, nested_count);
+                assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+                cells[(y * side as f64) as usize * side + (x * side as f64) as usize] += 1;
+            }
+        }
+        assert!(cells.iter().all(|&points| points == 1), "{:?} isn't stratified: {:?}", kind, cells);
+    }
+
+    #[test]
+    fn test_stratification() {
+        assert_stratified(SamplerKind::Stratified, 9, 1);
+        assert_stratified(SamplerKind::Stratified, 4, 4);
+        // A (0, 2) sequence is stratified on any power of 4 square grid
+        assert_stratified(SamplerKind::Sobol, 16, 1);
+        assert_stratified(SamplerKind::Sobol, 4, 16);
+    }
+
+    #[test]
+    fn test_samplers_are_deterministic() {
+        for kind in [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol].iter() {
+            let points = |seed: u64| {
+                let mut sampler = kind.create(seed);
+                sampler.start_pixel_sample(1, 4);
+                (sampler.sample_2d(0, 0, 1), sampler.sample_2d(1, 2, 9))
+            };
+            assert_eq!(points(7), points(7));
+            assert!(points(7) != points(8));
+        }
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut shuffle = Shuffle::new(1234);
+        // The same order, whichever element is asked for first
+        let last = shuffle.get(9, 10);
+        let mut values: Vec<u32> = (0..10).map(|index| shuffle.get(index, 10)).collect();
+        assert_eq!(values[9], last);
+        assert_eq!(values, (0..10).map(|index| Shuffle::new(1234).get(index, 10)).collect::<Vec<u32>>());
+        assert!(values != (0..10).collect::<Vec<u32>>());
+        values.sort();
+        assert_eq!(values, (0..10).collect::<Vec<u32>>());
+    }
+}
@@ -1,215 +1,367 @@
 use vector::Vector;
-use camera::Camera;
+use camera::{Camera, LENS_DIMENSION};
 use color::{Color, BLACK};
-use model_object::ModelObject;
-use material::Material;
-use sphere::Sphere;
-use plane::Plane;
-use triangle::Triangle;
 use color_image::ColorImage;
 use ray::Ray;
 use hit::Hit;
 use light::Light;
-use rand::{self, Rng};
-use std::io;
-use std::io::prelude::*;
-use std::io::BufReader;
-use std::fs::File;
+use bvh::Bvh;
+use scene_parser::{self, SceneParseError};
+use sampler::{self, Sampler, SamplerKind};
+use filter::Filter;
+use film::Film;
+use tile::{self, Tile};
+use std::error;
+use std::fmt;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use threadpool::ThreadPool;
-use std::sync::mpsc;
 
 pub struct Scene {
     pub background_color: Color,
     pub shadow_rays_n: u32,
     pub max_recursion: u32,
     pub super_sampling_n: u32,
-    pub objects: Vec<Box<ModelObject + Send + Sync>>,
+    /// Seeds the random sampling, renders with the same seed are identical
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    pub filter: Filter,
+    /// Width and height of the tiles the render threads take, in pixels
+    pub tile_size: u32,
+    pub objects: Bvh,
     pub camera: Camera,
     pub lights: Vec<Light>,
 }
 
-const RAY_SMALL_ADVANCEMENT: f64 = 0.000000001;
+/// Stops sampling a pixel once its estimated noise is low enough, instead of always taking
+/// all of the camera's samples
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Samples every pixel gets before its noise is estimated
+    pub min_samples: u32,
+    /// Standard error of the mean (displayed) luminance of a pixel under which sampling stops
+    pub noise_threshold: f64,
+}
 
-impl Scene {
-    pub fn from_file_path<P: AsRef<Path>>(file_path: P) -> io::Result<Scene> {
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
+pub struct RenderedTile {
+    pub tile: Tile,
+    /// The samples of the tile, also covering the neighboring pixels they contribute to
+    pub film: Film,
+    /// Row by row
+    pub sample_counts: Vec<u32>,
+}
 
-        let mut camera: Option<Camera> = None;
-        let mut settings: Option<(Color, u32, u32, u32)> = None;
-        let mut materials: Vec<Material> = Vec::new();
-        let mut objects: Vec<Box<ModelObject + Send + Sync>> = Vec::new();
-        let mut lights: Vec<Light> = Vec::new();
+/// The image being rendered, shared by the render threads
+struct RenderTarget {
+    film: Film,
+    sample_counts: Vec<u32>,
+    /// Finished tiles waiting for the tiles before them
+    pending_tiles: Vec<Option<RenderedTile>>,
+    merged_tiles: usize,
+}
 
-        for line in reader.lines() {
-            let line = line?;
-            let line = line.trim();
+impl RenderTarget {
+    /// Merges finished tiles in the order they were handed out, so that the sums of overlapping
+    /// tiles don't depend on which thread finished first
+    fn add_tile(&mut self, index: usize, rendered_tile: RenderedTile) {
+        self.pending_tiles[index] = Some(rendered_tile);
+        while let Some(rendered_tile) = self.pending_tiles.get_mut(self.merged_tiles).and_then(Option::take) {
+            self.merge(&rendered_tile);
+            self.merged_tiles += 1;
+        }
+    }
 
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
+    /// Merges the finished tiles that are still waiting, for a partial image
+    fn merge_pending_tiles(&mut self) {
+        let pending_tiles: Vec<RenderedTile> = self.pending_tiles.iter_mut().filter_map(Option::take).collect();
+        for rendered_tile in pending_tiles.iter() {
+            self.merge(rendered_tile);
+        }
+    }
 
-            let mut parts = &mut line.split_whitespace();
-            let item_type = parts.next().unwrap(); //Note: this can't fail because line is not empty
-            match item_type {
-                "cam" => {
-                    camera = Some(Camera::new(
-                        parse_vector(parts),
-                        parse_vector(parts),
-                        parse_vector(parts),
-                        parse_f64(parts),
-                        parse_f64(parts),
-                        500, //TODO
-                        500, //TODO
-                        1,
-                    ));
-                }
-                "set" => {
-                    settings = Some((
-                        parse_color(parts),
-                        parse_u32(parts),
-                        parse_u32(parts),
-                        parse_u32(parts),
-                    ));
-                }
-                "mtl" => {
-                    materials.push(Material::new(
-                        parse_color(parts),
-                        parse_color(parts),
-                        parse_color(parts),
-                        parse_f64(parts),
-                        parse_f64(parts),
-                    ));
-                }
-                "sph" => {
-                    objects.push(Box::new(Sphere {
-                        center: parse_vector(parts),
-                        radius: parse_f64(parts),
-                        material: materials[parse_u32(parts) as usize - 1],
-                    }));
-                }
-                "pln" => {
-                    objects.push(Box::new(Plane {
-                        normal: parse_vector(parts),
-                        offset: parse_f64(parts),
-                        material: materials[parse_u32(parts) as usize - 1],
-                    }));
-                }
-                "trg" => {
-                    objects.push(Box::new(Triangle::new(
-                        parse_vector(parts),
-                        parse_vector(parts),
-                        parse_vector(parts),
-                        materials[parse_u32(parts) as usize - 1],
-                    )));
-                }
-                "lgt" => {
-                    lights.push(Light::new(
-                        parse_vector(parts),
-                        parse_color(parts),
-                        parse_f64(parts),
-                        parse_f64(parts),
-                        parse_f64(parts),
-                    ));
-                }
-                _ => panic!("Unrecognized scene item") //TODO: return error
-            }
+    fn merge(&mut self, rendered_tile: &RenderedTile) {
+        self.film.merge(&rendered_tile.film);
+        let tile = rendered_tile.tile;
+        let image_width = self.film.width();
+        for (row, counts) in rendered_tile.sample_counts.chunks(tile.width as usize).enumerate() {
+            let start = (tile.top as usize + row) * image_width as usize + tile.left as usize;
+            self.sample_counts[start..start + counts.len()].copy_from_slice(counts);
         }
+    }
+}
 
-        let mut camera = camera.expect("Camera item not found"); //TODO
-        let settings = settings.expect("Settings item not found"); //TODO
-
-        let (background_color, shadow_rays_n, max_recursion, super_sampling_n) = settings;
-        camera.super_sampling_n = super_sampling_n;
-        Ok(Scene {
-            background_color,
-            shadow_rays_n,
-            max_recursion,
-            super_sampling_n,
-            objects,
-            camera,
-            lights,
-        })
+/// Stops a render early. Clones share the same state, so a clone can cancel from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderProgress {
+    pub finished_tiles: u32,
+    pub total_tiles: u32,
+    /// Fraction of the image's pixels that are rendered
+    pub fraction: f64,
+    pub elapsed: Duration,
+    /// Estimated time until the render is done, assuming the rest renders as fast as the rest
+    /// did so far
+    pub remaining: Duration,
+}
+
+/// Receives the progress of a render
+pub type ProgressCallback<'a> = Box<dyn FnMut(&RenderProgress) + 'a>;
+
+pub struct RenderOptions<'a> {
+    pub thread_count: usize,
+    /// Called after each finished tile, on the thread that renders the scene
+    pub progress: Option<ProgressCallback<'a>>,
+    /// Once cancelled, threads stop after their current row of pixels and the render returns the
+    /// tiles finished so far
+    pub cancellation: CancellationToken,
+}
+
+impl<'a> RenderOptions<'a> {
+    pub fn new(thread_count: usize) -> RenderOptions<'a> {
+        RenderOptions {
+            thread_count,
+            progress: None,
+            cancellation: CancellationToken::new(),
+        }
+    }
+}
+
+pub struct RenderedImage {
+    /// Pixels of unfinished tiles are black if the render was cancelled
+    pub color_image: ColorImage,
+    /// Number of samples taken for each pixel (row by row)
+    pub sample_counts: Vec<u32>,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RenderError {
+    NoThreads,
+    /// Some tiles were never delivered, e.g. because rendering them panicked
+    MissingTiles { missing_tiles: u32 },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RenderError::NoThreads => write!(f, "at least one render thread is required"),
+            RenderError::MissingTiles { missing_tiles } => write!(f, "{} image tiles failed to render", missing_tiles),
+        }
+    }
+}
+
+impl error::Error for RenderError {}
+
+const RAY_SMALL_ADVANCEMENT: f64 = 0.000000001;
+
+impl Scene {
+    pub fn from_file_path<P: AsRef<Path>>(file_path: P) -> Result<Scene, Vec<SceneParseError>> {
+        scene_parser::parse_scene_file(file_path)
     }
 
-    pub fn render(self, thread_count: usize) -> ColorImage {
+    pub fn set_super_sampling_n(&mut self, super_sampling_n: u32) {
+        self.super_sampling_n = super_sampling_n;
+        self.camera.set_super_sampling_n(super_sampling_n);
+    }
+
+    pub fn render(self, thread_count: usize) -> Result<ColorImage, RenderError> {
+        self.render_with_options(RenderOptions::new(thread_count)).map(|rendered_image| rendered_image.color_image)
+    }
+
+    pub fn render_with_options(self, mut options: RenderOptions) -> Result<RenderedImage, RenderError> {
+        if options.thread_count == 0 {
+            return Err(RenderError::NoThreads);
+        }
+        let start = Instant::now();
         let width = self.camera.image_width;
         let height = self.camera.image_height;
+        let tiles = Arc::new(tile::spiral_tiles(width, height, self.tile_size));
+        let target = Arc::new(Mutex::new(RenderTarget {
+            film: Film::new(width, height, self.filter),
+            sample_counts: vec![0; width as usize * height as usize],
+            pending_tiles: tiles.iter().map(|_| None).collect(),
+            merged_tiles: 0,
+        }));
+        let next_tile = Arc::new(AtomicUsize::new(0));
 
         let scene = Arc::new(self);
-        let pool = ThreadPool::new(thread_count);
+        let pool = ThreadPool::new(options.thread_count);
         let (tx, rx) = mpsc::channel();
-
-        for y in 0..height {
+        for _ in 0..options.thread_count {
             let thread_tx = tx.clone();
             let thread_scene = scene.clone();
+            let thread_tiles = tiles.clone();
+            let thread_target = target.clone();
+            let thread_next_tile = next_tile.clone();
+            let cancellation = options.cancellation.clone();
+            // Threads take the next tile whenever they finish one, so uneven tiles balance out
             pool.execute(move || {
-                thread_tx.send((y, thread_scene.render_row(y))).unwrap();
+                while !cancellation.is_cancelled() {
+                    let index = thread_next_tile.fetch_add(1, Ordering::SeqCst);
+                    let tile = match thread_tiles.get(index) {
+                        Some(&tile) => tile,
+                        None => break,
+                    };
+                    if let Some(rendered_tile) = thread_scene.render_tile(tile, &cancellation) {
+                        thread_target.lock().unwrap().add_tile(index, rendered_tile);
+                        thread_tx.send(tile.width as usize * tile.height as usize).unwrap();
+                    }
+                }
             })
         }
         drop(tx);
 
-        let mut color_image = ColorImage::new(width, height);
-        for (y, row) in rx.iter() {
-            &color_image.pixels[(y * width) as usize..((y + 1) * width) as usize].copy_from_slice(&row);
+        // Ends once every thread is done, whether it finished, was cancelled or panicked
+        let mut finished_pixels = 0;
+        for (tile_index, tile_pixels) in rx.iter().enumerate() {
+            finished_pixels += tile_pixels;
+            if let Some(ref mut progress) = options.progress {
+                let fraction = finished_pixels as f64 / (width as f64 * height as f64);
+                let elapsed = start.elapsed();
+                progress(&RenderProgress {
+                    finished_tiles: tile_index as u32 + 1,
+                    total_tiles: tiles.len() as u32,
+                    fraction,
+                    elapsed,
+                    remaining: elapsed.mul_f64((1.0 - fraction) / fraction),
+                });
+            }
         }
-        color_image
+
+        let mut target = target.lock().unwrap();
+        let cancelled = options.cancellation.is_cancelled();
+        if cancelled {
+            target.merge_pending_tiles();
+        } else if target.merged_tiles < target.pending_tiles.len() {
+            let missing_tiles = target.pending_tiles.iter().skip(target.merged_tiles).filter(|tile| tile.is_none()).count();
+            return Err(RenderError::MissingTiles { missing_tiles: missing_tiles as u32 });
+        }
+        Ok(RenderedImage {
+            color_image: target.film.to_color_image(),
+            sample_counts: target.sample_counts.clone(),
+            cancelled,
+        })
     }
 
-    pub fn render_row(&self, y: u32) -> Vec<Color> {
-        (0..self.camera.image_width).map(|x| self.render_pixel(x, y)).collect()
+    /// Renders a tile, onto a film that also covers the pixels around it that its samples can
+    /// contribute to. Returns None if cancelled before the tile is done.
+    pub fn render_tile(&self, tile: Tile, cancellation: &CancellationToken) -> Option<RenderedTile> {
+        let reach = self.filter.pixel_reach();
+        let left = tile.left.saturating_sub(reach);
+        let top = tile.top.saturating_sub(reach);
+        let right = (tile.left + tile.width + reach).min(self.camera.image_width);
+        let bottom = (tile.top + tile.height + reach).min(self.camera.image_height);
+        let mut film = Film::region(left, top, right - left, bottom - top, self.filter);
+        let mut sample_counts = Vec::with_capacity(tile.width as usize * tile.height as usize);
+        for y in tile.top..tile.top + tile.height {
+            if cancellation.is_cancelled() {
+                return None;
+            }
+            for x in tile.left..tile.left + tile.width {
+                sample_counts.push(self.render_pixel(x, y, &mut film));
+            }
+        }
+        Some(RenderedTile { tile, film, sample_counts })
     }
 
-    pub fn render_pixel(&self, x: u32, y: u32) -> Color {
-        let rays = self.camera.construct_rays_through_pixel(x, y);
-        let len = rays.len();
-        let total_color: Color = rays.map(|ray| self.color_ray_hits(&ray, 0)).sum();
-        total_color / len as f64
+    /// Adds the pixel's samples to the film, returning the number of samples taken
+    pub fn render_pixel(&self, x: u32, y: u32, film: &mut Film) -> u32 {
+        let mut sampler = self.sampler.create(sampler::pixel_seed(self.seed, x, y));
+        let max_samples = self.camera.samples_per_pixel();
+        let min_samples = self.adaptive_sampling.map_or(max_samples, |adaptive| adaptive.min_samples.clamp(1, max_samples));
+        // Running mean and sum of squared differences of the luminance (Welford's algorithm)
+        let mut mean = 0.0;
+        let mut squared_differences = 0.0;
+        let mut sample_count = 0;
+        while sample_count < max_samples {
+            sampler.start_pixel_sample(sample_count, max_samples);
+            let (image_x, image_y) = self.camera.pixel_sample_position(x, y, &mut *sampler);
+            let color = match self.camera.construct_pixel_sample_ray(image_x, image_y, &mut *sampler) {
+                Some(ray) => self.color_ray_hits(&ray, 0, &mut *sampler),
+                None => BLACK,
+            };
+            film.add_sample(image_x, image_y, color);
+            sample_count += 1;
+
+            if let Some(adaptive) = self.adaptive_sampling {
+                let luminance = color.clamped().luminance();
+                let difference = luminance - mean;
+                mean += difference / sample_count as f64;
+                squared_differences += difference * (luminance - mean);
+                if sample_count >= min_samples && sample_count > 1 {
+                    let variance = squared_differences / (sample_count - 1) as f64;
+                    if (variance / sample_count as f64).sqrt() <= adaptive.noise_threshold {
+                        break;
+                    }
+                }
+            }
+        }
+        sample_count
     }
 
-    pub fn color_ray_hits(&self, ray: &Ray, recursion_level: u32) -> Color {
+    pub fn color_ray_hits(&self, ray: &Ray, recursion_level: u32, sampler: &mut dyn Sampler) -> Color {
         let new_recursion_level = recursion_level + 1;
         if new_recursion_level > self.max_recursion {
             return self.background_color;
         }
-        let hits = self.find_hits(ray);
-        self.color_hits(&hits, recursion_level)
-    }
-
-    pub fn find_hits(&self, ray: &Ray) -> Vec<Hit> {
-        let mut hits: Vec<Hit> = self.objects.iter()
-            .filter_map(|object| object.try_hit(ray))
-            .collect::<Vec<Hit>>();
-        //TODO: handle NANs somehow?
-        hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
-        hits
-    }
-
-    pub fn color_hits(&self, hits: &Vec<Hit>, recursion_level: u32) -> Color {
-        let mut total_color = BLACK;
-        let mut prev_transparency = 1f64;
-        for hit in hits {
-            let current_transparency = hit.object.material().transparency;
-            let direct = self.get_hit_direct_color(hit) * (1f64 - current_transparency);
-            let reflection = self.get_hit_reflection_color(hit, recursion_level);
-            let color = (direct + reflection) * prev_transparency;
-            total_color += color;
-            prev_transparency *= current_transparency;
-            if current_transparency == 0f64 {
-                return total_color;
-            }
-        }
+        self.color_ray_hits_through(ray, new_recursion_level, sampler)
+    }
 
-        total_color + self.background_color * prev_transparency
+    /// Colors the closest hit, blending in whatever is seen through it according to its transparency.
+    /// Refracted rays don't count as a recursion level, reflected rays do.
+    fn color_ray_hits_through(&self, ray: &Ray, recursion_level: u32, sampler: &mut dyn Sampler) -> Color {
+        let hit = match self.objects.find_closest_hit(ray) {
+            Some(hit) => hit,
+            None => return self.background_color,
+        };
+        let material = hit.object.material();
+        let mut color = BLACK;
+        let mut reflection_weight = BLACK;
+        // The inner side of a closed transparent object only refracts and reflects
+        if hit.entering || !material.is_transparent() || !hit.object.is_closed() {
+            color += self.get_hit_direct_color(&hit, recursion_level, sampler) * (1f64 - material.transparency);
+            reflection_weight += material.reflection_color;
+        }
+        if material.is_transparent() {
+            let (refraction_color, reflectance) = self.get_hit_refraction(&hit, recursion_level, sampler);
+            color += refraction_color * material.transparency;
+            let fresnel_weight = reflectance * material.transparency;
+            reflection_weight += Color::new(fresnel_weight, fresnel_weight, fresnel_weight);
+        }
+        if reflection_weight != BLACK {
+            color += self.get_hit_reflection_color(&hit, recursion_level, sampler) * reflection_weight;
+        }
+        color
     }
 
-    fn get_hit_direct_color(&self, hit: &Hit) -> Color {
+    fn get_hit_direct_color(&self, hit: &Hit, recursion_level: u32, sampler: &mut dyn Sampler) -> Color {
         let mut total_diffuse_component = BLACK;
         let mut total_specular_component = BLACK;
-        for light in self.lights.iter() {
-            let light_intensity = self.get_light_intensity_for_hit(light, hit);
+        for (light_index, light) in self.lights.iter().enumerate() {
+            // Each light at each recursion level gets its own set of sample points
+            let dimension = LENS_DIMENSION + 1 + (recursion_level - 1) * self.lights.len() as u32 + light_index as u32;
+            let light_intensity = self.get_light_intensity_for_hit(light, hit, dimension, sampler);
             if light_intensity == 0.0 {
                 continue;
             }
@@ -241,20 +393,45 @@ impl Scene {
         total_diffuse_component + total_specular_component
     }
 
-    fn get_hit_reflection_color(&self, hit: &Hit, recursion_level: u32) -> Color {
-        if !hit.object.material().is_reflective() {
-            return BLACK;
-        }
+    /// Returns the color of the ray refracted through the hit surface, already weighted by the
+    /// transmitted fraction, and the Fresnel reflectance (1 on total internal reflection)
+    fn get_hit_refraction(&self, hit: &Hit, recursion_level: u32, sampler: &mut dyn Sampler) -> (Color, f64) {
+        let refractive_index = hit.object.material().refractive_index;
+        let (from_index, to_index) = if !hit.object.is_closed() {
+            // An open surface is a thin sheet, which doesn't bend the rays passing through it
+            (1.0, 1.0)
+        } else if hit.entering {
+            (1.0, refractive_index)
+        } else {
+            (refractive_index, 1.0)
+        };
+        let direction = -hit.direction_to_source;
+        let refraction_direction = match direction.refract_through(&hit.hit_normal, from_index / to_index) {
+            Some(refraction_direction) => refraction_direction,
+            None => return (BLACK, 1.0),
+        };
+        let cos_incident = hit.hit_normal % hit.direction_to_source;
+        let cos_transmitted = -(hit.hit_normal % refraction_direction);
+        let reflectance = schlick_reflectance(cos_incident, cos_transmitted, from_index, to_index);
+
+        let mut refraction_ray = Ray::new(hit.hit_point, refraction_direction);
+        // Move past the hit surface to avoid hitting it again
+        refraction_ray.advance(RAY_SMALL_ADVANCEMENT);
+        let refraction_color = self.color_ray_hits_through(&refraction_ray, recursion_level, sampler);
+        (refraction_color * (1.0 - reflectance), reflectance)
+    }
+
+    /// Returns the (untinted) color seen in the mirror direction of the hit
+    fn get_hit_reflection_color(&self, hit: &Hit, recursion_level: u32, sampler: &mut dyn Sampler) -> Color {
         let hit_reflection_direction = hit.direction_to_source.reflect_around(&hit.hit_normal);
         debug_assert!(::utils::almost_eq(hit_reflection_direction.norm(), 1.0));
         let mut reflection_ray = Ray::new(hit.hit_point, hit_reflection_direction);
         // Move reflection exit point forward a bit to avoid numeric issues (hitting the same surface)
         reflection_ray.advance(RAY_SMALL_ADVANCEMENT);
-        let reflection_color = self.color_ray_hits(&reflection_ray, recursion_level);
-        reflection_color * hit.object.material().reflection_color
+        self.color_ray_hits(&reflection_ray, recursion_level, sampler)
     }
 
-    fn get_light_intensity_for_hit(&self, light: &Light, hit: &Hit) -> f64 {
+    fn get_light_intensity_for_hit(&self, light: &Light, hit: &Hit, dimension: u32, sampler: &mut dyn Sampler) -> f64 {
         let light_direction = hit.hit_point.direction_to(&light.position);
         let direction_x = if light_direction.x == 0.0 && light_direction.y == 0.0 {
             Vector::new(1.0, 0.0, 0.0)
@@ -264,38 +441,35 @@ impl Scene {
         };
         let direction_y = *(light_direction ^ direction_x).normalize();
 
-        let n = self.shadow_rays_n;
-        let cell_radius = light.radius / n as f64;
-        let half_radius = cell_radius / 2.0;
-
+        let sample_count = self.shadow_rays_n * self.shadow_rays_n;
         let mut total_intensity = 0.0;
-        let mut rng = rand::thread_rng(); //TODO: switch to fast RNG, with hardcoded seed...
-        for x in 0..n {
-            for y in 0..n {
-                let x_offset = cell_radius * (x as f64 + rng.next_f64()) - half_radius;
-                let y_offset = cell_radius * (y as f64 + rng.next_f64()) - half_radius;
-                let x_delta = direction_x * x_offset;
-                let y_delta = direction_y * y_offset;
-                let cell_point = light.position + x_delta + y_delta;
-                let ray = Ray::construct_ray(cell_point, hit.hit_point);
-                total_intensity += self.get_ray_intensity(hit, &ray);
-            }
+        for index in 0..sample_count {
+            let (u, v) = sampler.sample_2d(dimension, index, sample_count);
+            let cell_point = match light.quad {
+                Some(ref quad) => quad.point_at(u, v),
+                None => {
+                    // A point on the square of the light's width, centered on the light
+                    let x_delta = direction_x * (light.radius * (u - 0.5));
+                    let y_delta = direction_y * (light.radius * (v - 0.5));
+                    light.position + x_delta + y_delta
+                }
+            };
+            let ray = Ray::construct_ray(cell_point, hit.hit_point);
+            total_intensity += self.get_ray_intensity(hit, &ray);
         }
 
-        let intensity = total_intensity / (n * n) as f64;
+        let intensity = total_intensity / sample_count as f64;
         // Interpolate intensity such that the minimum is shadow_intensity
-        let intensity = 1.0 - ((1.0 - intensity) * light.shadow_intensity);
-        return intensity;
+        1.0 - ((1.0 - intensity) * light.shadow_intensity)
     }
 
     fn get_ray_intensity(&self, hit: &Hit, ray: &Ray) -> f64 {
         let mut ray_intensity = 1.0;
         let max_hit_distance = hit.hit_point.distance_to(&ray.position) + ::utils::EPSILON;
-        for ray_hit in self.find_hits(ray) {
-            //TODO: make sure this short circuits
+        for ray_hit in self.objects.find_hits_within(ray, max_hit_distance) {
             // Check if we got to the given hit or if we passed it
             // (at object edges ray_hit can miss the original ray hit)
-            let objects_equal = ray_hit.object as *const _ == hit.object as *const _;
+            let objects_equal = std::ptr::eq(ray_hit.object, hit.object);
             let hits_almost_equal = objects_equal && ray_hit.hit_point.almost_equal_to(&hit.hit_point, ::utils::EPSILON);
             let passed_max_distance = ray_hit.hit_point.distance_to(&ray.position) > max_hit_distance;
             if hits_almost_equal || passed_max_distance {
@@ -306,30 +480,24 @@ impl Scene {
                 ray_intensity = 0.0;
                 break;
             }
-            // Attenuate intensity
-            ray_intensity *= ray_hit.object.material().transparency;
+            // Attenuate intensity, once per transparent object crossed, and at every open surface
+            if ray_hit.entering || !ray_hit.object.is_closed() {
+                ray_intensity *= ray_hit.object.material().transparency;
+            }
         }
         ray_intensity
     }
 }
 
-//TODO: fix error handling
-fn parse_f64<'a, I: Iterator<Item=&'a str>>(params: &mut I) -> f64 {
-    let param = params.next().expect("not enough params");
-    param.parse().expect("expected float")
-}
-
-fn parse_u32<'a, I: Iterator<Item=&'a str>>(params: &mut I) -> u32 {
-    let param = params.next().expect("not enough params");
-    param.parse().expect("expected unsigned integer")
-}
-
-fn parse_vector<'a, I: Iterator<Item=&'a str>>(params: &mut I) -> Vector {
-    Vector::new(parse_f64(params), parse_f64(params), parse_f64(params))
-}
-
-fn parse_color<'a, I: Iterator<Item=&'a str>>(params: &mut I) -> Color {
-    Color::new(parse_f64(params), parse_f64(params), parse_f64(params))
+/// Schlick's approximation of the Fresnel reflectance between two media
+fn schlick_reflectance(cos_incident: f64, cos_transmitted: f64, from_index: f64, to_index: f64) -> f64 {
+    if from_index == to_index {
+        return 0.0;
+    }
+    let r0 = ((from_index - to_index) / (from_index + to_index)).powi(2);
+    // Use the angle on the optically thinner side
+    let cos_angle = if from_index <= to_index { cos_incident } else { cos_transmitted };
+    r0 + (1.0 - r0) * (1.0 - cos_angle).powi(5)
 }
 
 #[cfg(test)]
@@ -339,19 +507,20 @@ mod tests {
     use std::fs;
     use std::time::Instant;
     use num_cpus;
+    use utils::almost_eq;
 
     fn test_scene(file_name: &str) {
         let scene_path: PathBuf = ["scenes", file_name].iter().collect();
         let scene = Scene::from_file_path(scene_path).expect("Could not create scene");
         let start = Instant::now();
-        let color_image = scene.render(num_cpus::get());
+        let color_image = scene.render(num_cpus::get()).expect("Could not render scene");
         let duration = start.elapsed();
         let duration = duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9;
         println!("Rendered scene {} in {:.3} seconds", file_name, duration);
         let image_buffer = color_image.to_image_buffer();
         let mut output_path: PathBuf = ["outputs", file_name].iter().collect();
         output_path.set_extension("png");
-        fs::create_dir_all(&output_path.parent().unwrap()).expect("Could not create output directory");
+        fs::create_dir_all(output_path.parent().unwrap()).expect("Could not create output directory");
         image_buffer.save(&output_path).expect("Could not save output");
     }
 
@@ -389,4 +558,228 @@ mod tests {
     fn test_transparency() {
         test_scene("Transparency.txt");
     }
+
+    #[test]
+    fn test_refraction() {
+        test_scene("Refraction.txt");
+    }
+
+    #[test]
+    fn test_mesh() {
+        test_scene("Mesh.txt");
+    }
+
+    #[test]
+    fn test_boxes() {
+        test_scene("Boxes.txt");
+    }
+
+    #[test]
+    fn test_shapes() {
+        test_scene("Shapes.txt");
+    }
+
+    #[test]
+    fn test_torus() {
+        test_scene("Torus.txt");
+    }
+
+    #[test]
+    fn test_quads() {
+        test_scene("Quads.txt");
+    }
+
+    #[test]
+    fn test_csg() {
+        test_scene("Csg.txt");
+    }
+
+    #[test]
+    fn test_open_surfaces_are_two_sided() {
+        let scene = |triangle: &str| {
+            let text = format!("\
+cam 0 0 0 0 0 1 0 1 0 1 1
+set 0 0 0 1 5 1
+mtl 1 0 0 0 0 0 0 0 0 1 0.5
+mtl 0 0 1 0 0 0 0 0 0 1 0
+trg {} 1
+pln 0 0 -1 -4 2
+lgt 0 0 -1 1 1 1 1 1 0
+", triangle);
+            let mut scene = scene_parser::parse_scene(text.as_bytes(), Path::new("test.txt")).expect("Could not parse scene");
+            scene.camera.set_resolution(8, 8);
+            scene
+        };
+        // The same transparent triangle, facing towards and away from the camera and the light
+        let front = scene("-1 -1 2 0 1 2 1 -1 2");
+        let back = scene("-1 -1 2 1 -1 2 0 1 2");
+        let triangle_hit = back.objects.find_closest_hit(&Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))).expect("Expected a hit");
+        assert!(!triangle_hit.entering);
+
+        // Shadows through it are attenuated from either side
+        let plane_hit = back.objects.find_closest_hit(&Ray::new(Vector::new(0.0, 0.0, 3.0), Vector::new(0.0, 0.0, 1.0))).expect("Expected a hit");
+        assert!(almost_eq(back.get_ray_intensity(&plane_hit, &Ray::construct_ray(Vector::new(0.0, 0.0, -1.0), plane_hit.hit_point)), 0.5));
+
+        // And it is shaded the same from either side
+        let front_pixels = front.render(1).expect("Could not render scene").pixels;
+        let back_pixels = back.render(1).expect("Could not render scene").pixels;
+        let center = 4 * 8 + 4;
+        assert!(front_pixels[center].r > 0.0);
+        for (front_color, back_color) in front_pixels.iter().zip(back_pixels.iter()) {
+            assert!(almost_eq(front_color.r, back_color.r) && almost_eq(front_color.b, back_color.b));
+        }
+    }
+
+    #[test]
+    fn test_transparent_planes_refract() {
+        let scene = |surface: &str| {
+            let text = format!("\
+cam 0 0 0 0 0 1 0 1 0 1 1
+set 0 0 0 1 5 1
+mtl 0 0 0 0 0 0 0 0 0 1 1 1.5
+mtl 1 0 0 0 0 0 0 0 0 1 0
+mtl 0 0 1 0 0 0 0 0 0 1 0
+{} 1
+sph 6 0 6 1 2
+pln 0 0 -1 -10 3
+lgt 0 0 1 1 1 1 1 1 0
+", surface);
+            scene_parser::parse_scene(text.as_bytes(), Path::new("test.txt")).expect("Could not parse scene")
+        };
+        // At 45 degrees, the sphere is straight ahead, and out of the way of a ray bent towards the normal
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 1.0).normalized());
+        let color = |scene: &Scene| scene.color_ray_hits(&ray, 0, &mut *scene.sampler.create(0));
+
+        // The solid behind a transparent plane bends the ray, so it misses the sphere
+        let plane = scene("pln 0 0 -1 -2");
+        let color_through_plane = color(&plane);
+        assert!(color_through_plane.b > 0.0 && color_through_plane.r == 0.0);
+        // A thin sheet in the same place lets it through straight
+        let sheet = scene("qad -10 -10 2 20 0 0 0 20 0");
+        let color_through_sheet = color(&sheet);
+        assert!(color_through_sheet.r > 0.0 && color_through_sheet.b == 0.0);
+    }
+
+    #[test]
+    fn test_renders_are_reproducible() {
+        let render = |thread_count: usize, seed: u64| {
+            let mut scene = Scene::from_file_path("scenes/DepthOfField.txt").expect("Could not create scene");
+            scene.camera.set_resolution(60, 40);
+            scene.seed = seed;
+            scene.render(thread_count).expect("Could not render scene").pixels
+        };
+        assert!(render(1, 1) == render(3, 1));
+        // The lens samples depend on the seed
+        assert!(render(1, 1) != render(1, 2));
+    }
+
+    #[test]
+    fn test_adaptive_sampling() {
+        let mut scene = Scene::from_file_path("scenes/Simple.txt").expect("Could not create scene");
+        scene.camera.set_resolution(40, 40);
+        scene.set_super_sampling_n(4);
+        scene.adaptive_sampling = Some(AdaptiveSampling { min_samples: 4, noise_threshold: 0.001 });
+        let sample_counts = scene.render_with_options(RenderOptions::new(1)).expect("Could not render scene").sample_counts;
+        assert!(sample_counts.iter().all(|count| (4..=16).contains(count)));
+        // Flat areas stop early, edges don't
+        assert!(sample_counts.contains(&4));
+        assert!(sample_counts.contains(&16));
+    }
+
+    #[test]
+    fn test_progress_and_cancellation() {
+        let scene = || {
+            let mut scene = Scene::from_file_path("scenes/Simple.txt").expect("Could not create scene");
+            scene.camera.set_resolution(64, 64);
+            scene.tile_size = 16;
+            scene
+        };
+        let mut updates = Vec::new();
+        {
+            let mut options = RenderOptions::new(2);
+            options.progress = Some(Box::new(|progress: &RenderProgress| updates.push(*progress)));
+            assert!(!scene().render_with_options(options).expect("Could not render scene").cancelled);
+        }
+        assert_eq!(updates.iter().map(|progress| progress.finished_tiles).collect::<Vec<u32>>(), (1..=16).collect::<Vec<u32>>());
+        assert!(updates.iter().all(|progress| progress.total_tiles == 16));
+        assert_eq!(updates.last().unwrap().fraction, 1.0);
+
+        // Threads stop before their next tile, leaving the rest of the image empty
+        let options = RenderOptions::new(2);
+        options.cancellation.cancel();
+        let rendered_image = scene().render_with_options(options).expect("Could not render scene");
+        assert!(rendered_image.cancelled);
+        assert!(rendered_image.sample_counts.iter().all(|&count| count == 0));
+        assert!(rendered_image.color_image.pixels.iter().all(|&color| color == BLACK));
+
+        // Cancelled from the progress callback after the first tile. With many small tiles the
+        // single thread can't get through all of them before it sees the cancellation.
+        let mut scene = scene();
+        scene.camera.set_resolution(256, 256);
+        scene.tile_size = 4;
+        let total_pixels = 256 * 256;
+        let mut updates = Vec::new();
+        let cancellation = CancellationToken::new();
+        let rendered_image = {
+            let mut options = RenderOptions::new(1);
+            options.cancellation = cancellation.clone();
+            options.progress = Some(Box::new(|progress: &RenderProgress| {
+                cancellation.cancel();
+                updates.push(*progress);
+            }));
+            scene.render_with_options(options).expect("Could not render scene")
+        };
+        assert!(rendered_image.cancelled);
+        let last_update = updates.last().expect("Expected progress");
+        assert!(last_update.finished_tiles < last_update.total_tiles);
+        // Exactly the pixels of the reported tiles are rendered
+        let rendered_pixels = rendered_image.sample_counts.iter().filter(|&&count| count > 0).count();
+        assert!(rendered_pixels > 0 && rendered_pixels < total_pixels);
+        assert_eq!(rendered_pixels as f64 / total_pixels as f64, last_update.fraction);
+    }
+
+    #[test]
+    fn test_depth_of_field() {
+        test_scene("DepthOfField.txt");
+    }
+
+    #[test]
+    fn test_sampler() {
+        test_scene("Sampler.txt");
+    }
+
+    #[test]
+    fn test_adaptive_sampling_scene() {
+        test_scene("AdaptiveSampling.txt");
+    }
+
+    #[test]
+    fn test_filter() {
+        test_scene("Filter.txt");
+    }
+
+    #[test]
+    fn test_orthographic() {
+        test_scene("Orthographic.txt");
+    }
+
+    #[test]
+    fn test_panorama() {
+        test_scene("Panorama.txt");
+    }
+
+    #[test]
+    fn test_fisheye() {
+        test_scene("Fisheye.txt");
+    }
+
+    #[test]
+    fn test_lens_distortion() {
+        test_scene("LensDistortion.txt");
+    }
+
+    #[test]
+    fn test_stereo() {
+        test_scene("Stereo.txt");
+    }
 }
\ No newline at end of file
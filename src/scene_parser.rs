@@ -0,0 +1,1119 @@
+use vector::Vector;
+use camera::{Camera, MAX_SUPER_SAMPLING_N, FieldOfView, Projection, FisheyeMapping, LensDistortion, Stereo, StereoLayout};
+use color::Color;
+use model_object::ModelObject;
+use material::Material;
+use sphere::Sphere;
+use plane::Plane;
+use triangle::Triangle;
+use cuboid::Cuboid;
+use disk::Disk;
+use cylinder::Cylinder;
+use cone::Cone;
+use torus::Torus;
+use quad::Quad;
+use csg::{Csg, CsgOperation};
+use transform::Transform;
+use obj_loader::{self, ObjError};
+use light::Light;
+use bvh::Bvh;
+use scene::{Scene, AdaptiveSampling};
+use sampler::SamplerKind;
+use filter::{Filter, FilterKind};
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Position of a token in a scene file. Lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub enum SceneParseError {
+    Io {
+        file: PathBuf,
+        error: io::Error,
+    },
+    UnknownItem {
+        location: Location,
+        item: String,
+    },
+    MissingParam {
+        location: Location,
+        item: String,
+        param: &'static str,
+    },
+    InvalidParam {
+        location: Location,
+        param: &'static str,
+        token: String,
+        reason: &'static str,
+    },
+    InvalidMaterialIndex {
+        location: Location,
+        token: String,
+        material_count: usize,
+    },
+    UnexpectedParam {
+        location: Location,
+        item: String,
+        token: String,
+    },
+    MissingItem {
+        file: PathBuf,
+        item: &'static str,
+    },
+    /// A mesh file that could not be read, as opposed to one that is malformed
+    MeshIo {
+        location: Location,
+        path: PathBuf,
+        error: io::Error,
+    },
+    InvalidMesh {
+        location: Location,
+        path: PathBuf,
+        error: ObjError,
+    },
+    /// A 'csg' item that isn't followed by the two objects it combines
+    MissingOperand {
+        location: Location,
+    },
+}
+
+impl SceneParseError {
+    pub fn location(&self) -> Option<&Location> {
+        match *self {
+            SceneParseError::UnknownItem { ref location, .. } |
+            SceneParseError::MissingParam { ref location, .. } |
+            SceneParseError::InvalidParam { ref location, .. } |
+            SceneParseError::InvalidMaterialIndex { ref location, .. } |
+            SceneParseError::UnexpectedParam { ref location, .. } |
+            SceneParseError::MeshIo { ref location, .. } |
+            SceneParseError::InvalidMesh { ref location, .. } |
+            SceneParseError::MissingOperand { ref location } => Some(location),
+            SceneParseError::Io { .. } | SceneParseError::MissingItem { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+impl fmt::Display for SceneParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SceneParseError::Io { ref file, ref error } =>
+                write!(f, "{}: could not read scene file: {}", file.display(), error),
+            SceneParseError::UnknownItem { ref location, ref item } =>
+                write!(f, "{}: unrecognized scene item '{}'", location, item),
+            SceneParseError::MissingParam { ref location, ref item, param } =>
+                write!(f, "{}: '{}' is missing parameter '{}'", location, item, param),
+            SceneParseError::InvalidParam { ref location, param, ref token, reason } =>
+                write!(f, "{}: invalid value '{}' for parameter '{}': {}", location, token, param, reason),
+            SceneParseError::InvalidMaterialIndex { ref location, ref token, material_count } =>
+                write!(f, "{}: invalid material index '{}': {} materials are defined at this point (indexes start at 1)",
+                       location, token, material_count),
+            SceneParseError::UnexpectedParam { ref location, ref item, ref token } =>
+                write!(f, "{}: unexpected extra parameter '{}' for '{}'", location, token, item),
+            SceneParseError::MissingItem { ref file, item } =>
+                write!(f, "{}: scene has no '{}' item", file.display(), item),
+            SceneParseError::MeshIo { ref location, ref path, ref error } =>
+                write!(f, "{}: could not read mesh file {}: {}", location, path.display(), error),
+            SceneParseError::InvalidMesh { ref location, ref path, ref error } =>
+                write!(f, "{}: could not load mesh {}: {}", location, path.display(), error),
+            SceneParseError::MissingOperand { ref location } =>
+                write!(f, "{}: 'csg' needs two objects after it to combine", location),
+        }
+    }
+}
+
+impl error::Error for SceneParseError {}
+
+/// Parses a scene file, collecting every error in it rather than stopping at the first one
+pub fn parse_scene_file<P: AsRef<Path>>(file_path: P) -> Result<Scene, Vec<SceneParseError>> {
+    let file_path = file_path.as_ref();
+    let file = File::open(file_path).map_err(|error| vec![SceneParseError::Io { file: file_path.to_path_buf(), error }])?;
+    parse_scene(BufReader::new(file), file_path)
+}
+
+/// Parses scene text from `reader`. `file_path` is only used for error locations.
+pub fn parse_scene<R: BufRead>(reader: R, file_path: &Path) -> Result<Scene, Vec<SceneParseError>> {
+    let mut state = ParseState {
+        camera: None,
+        settings: None,
+        resolution: None,
+        projection: None,
+        focus: None,
+        distortion: None,
+        stereo: None,
+        seed: None,
+        sampler: None,
+        adaptive_sampling: None,
+        filter: None,
+        tile_size: None,
+        materials: Vec::new(),
+        objects: Vec::new(),
+        lights: Vec::new(),
+        open_csgs: Vec::new(),
+        seen_camera: false,
+        seen_settings: false,
+    };
+    let mut errors = Vec::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                errors.push(SceneParseError::Io { file: file_path.to_path_buf(), error });
+                return Err(errors);
+            }
+        };
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let mut params = Params::new(file_path, line_index + 1, &line);
+        if let Err(error) = state.parse_item(&mut params).and_then(|_| params.finish()) {
+            errors.push(error);
+        }
+    }
+
+    for csg in state.open_csgs.iter() {
+        errors.push(SceneParseError::MissingOperand { location: csg.location.clone() });
+    }
+    // Only complain about missing items if they weren't attempted, to avoid duplicate errors
+    if !state.seen_camera {
+        errors.push(SceneParseError::MissingItem { file: file_path.to_path_buf(), item: "cam" });
+    }
+    if !state.seen_settings {
+        errors.push(SceneParseError::MissingItem { file: file_path.to_path_buf(), item: "set" });
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut camera = state.camera.unwrap();
+    if let Some((image_width, image_height, _)) = state.resolution {
+        camera.set_resolution(image_width, image_height);
+    }
+    if let Some(projection) = state.projection {
+        camera.projection = projection;
+    }
+    camera.distortion = state.distortion;
+    camera.set_stereo(state.stereo);
+    // Orthographic rays start on the screen, so the focal plane has to be beyond it
+    if let Some((param, token, location)) = state.focus {
+        if camera.projection == Projection::Orthographic && camera.aperture_radius > 0.0 && camera.focal_distance <= camera.screen_distance {
+            return Err(vec![SceneParseError::InvalidParam {
+                location,
+                param,
+                token,
+                reason: "expected the focal plane to be beyond the screen, where orthographic rays start",
+            }]);
+        }
+    }
+    if camera.projection == Projection::CubeMap && !camera.has_cube_map_aspect() {
+        match state.resolution {
+            Some((image_width, _, location)) => {
+                return Err(vec![SceneParseError::InvalidParam {
+                    location,
+                    param: "image_width",
+                    token: image_width.to_string(),
+                    reason: "expected 6 times the height (of each view, for stereo) for the square faces of a cube map",
+                }]);
+            }
+            None => camera.set_cube_map_resolution(DEFAULT_IMAGE_HEIGHT),
+        }
+    }
+    let (background_color, shadow_rays_n, max_recursion, super_sampling_n) = state.settings.unwrap();
+    let mut scene = Scene {
+        background_color,
+        shadow_rays_n,
+        max_recursion,
+        super_sampling_n,
+        seed: state.seed.unwrap_or(0),
+        sampler: state.sampler.unwrap_or(SamplerKind::Stratified),
+        adaptive_sampling: state.adaptive_sampling,
+        filter: state.filter.unwrap_or_default(),
+        tile_size: state.tile_size.unwrap_or(DEFAULT_TILE_SIZE),
+        objects: Bvh::new(state.objects),
+        camera,
+        lights: state.lights,
+    };
+    scene.set_super_sampling_n(super_sampling_n);
+    Ok(scene)
+}
+
+/// Resolution used when the scene has no 'res' item
+pub const DEFAULT_IMAGE_WIDTH: u32 = 500;
+pub const DEFAULT_IMAGE_HEIGHT: u32 = 500;
+/// Tile size used when the scene has no 'til' item
+pub const DEFAULT_TILE_SIZE: u32 = 16;
+
+struct ParseState {
+    camera: Option<Camera>,
+    settings: Option<(Color, u32, u32, u32)>,
+    /// Width, height and the location of the width
+    resolution: Option<(u32, u32, Location)>,
+    projection: Option<Projection>,
+    /// Parameter, token and location of the 'cam' focal distance or point, checked once the projection is known
+    focus: Option<(&'static str, String, Location)>,
+    distortion: Option<LensDistortion>,
+    stereo: Option<Stereo>,
+    seed: Option<u64>,
+    sampler: Option<SamplerKind>,
+    adaptive_sampling: Option<AdaptiveSampling>,
+    filter: Option<Filter>,
+    tile_size: Option<u32>,
+    materials: Vec<Material>,
+    objects: Vec<Box<dyn ModelObject + Send + Sync>>,
+    lights: Vec<Light>,
+    /// 'csg' items still waiting for their operands, innermost last
+    open_csgs: Vec<OpenCsg>,
+    seen_camera: bool,
+    seen_settings: bool,
+}
+
+/// A 'csg' item, whose operands are the next two objects in the file
+struct OpenCsg {
+    operation: CsgOperation,
+    location: Location,
+    /// Index in `ParseState::objects` of its first operand
+    first_operand: usize,
+}
+
+impl ParseState {
+    fn parse_item(&mut self, params: &mut Params) -> Result<(), SceneParseError> {
+        let first_new_object = self.objects.len();
+        match params.item {
+            "cam" => {
+                self.seen_camera = true;
+                let position = params.vector("position")?;
+                let look_at = params.distinct_vector("look_at", &position)?;
+                let column = params.tokens.get(params.next_token).map_or(params.end_column, |&(column, _)| column);
+                let up = params.vector("up")?;
+                if ((look_at - position) ^ up).norm_squared() == 0.0 {
+                    let token = params.tokens[params.next_token - 3].1;
+                    return Err(params.invalid(column, "up", token, "expected a direction that isn't parallel to the view direction"));
+                }
+                let mut camera = Camera::with_field_of_view(
+                    position,
+                    look_at,
+                    up,
+                    parse_field_of_view(params)?,
+                    DEFAULT_IMAGE_WIDTH,
+                    DEFAULT_IMAGE_HEIGHT,
+                    1,
+                );
+                // Optional depth of field: "aperture focal_distance" or "aperture fx fy fz" to focus on a point
+                if !params.is_empty() {
+                    let aperture_radius = params.non_negative_f64("aperture_radius")?;
+                    if params.remaining() >= 3 {
+                        camera.aperture_radius = aperture_radius;
+                        camera.focus_on(params.vector("focus_point")?);
+                        let (column, token) = params.tokens[params.next_token - 3];
+                        if camera.focal_distance <= 0.0 {
+                            return Err(params.invalid(column, "focus_point", token, "expected a point in front of the camera"));
+                        }
+                        self.focus = Some(("focus_point", token.to_string(), params.location(column)));
+                    } else {
+                        camera.set_depth_of_field(aperture_radius, params.positive_f64("focal_distance")?);
+                        let (column, token) = params.tokens[params.next_token - 1];
+                        self.focus = Some(("focal_distance", token.to_string(), params.location(column)));
+                    }
+                }
+                self.camera = Some(camera);
+            }
+            "prj" => {
+                self.projection = Some(parse_projection(params)?);
+            }
+            "dst" => {
+                self.distortion = Some(LensDistortion {
+                    k1: params.f64("k1")?,
+                    k2: params.f64("k2")?,
+                    p1: params.f64("p1")?,
+                    p2: params.f64("p2")?,
+                    k3: if params.is_empty() { 0.0 } else { params.f64("k3")? },
+                });
+            }
+            "ste" => {
+                let interocular_distance = params.positive_f64("interocular_distance")?;
+                let convergence_distance = params.positive_f64("convergence_distance")?;
+                let layout = if params.is_empty() {
+                    StereoLayout::SideBySide
+                } else {
+                    let (column, token) = params.token("layout")?;
+                    match token {
+                        "side-by-side" => StereoLayout::SideBySide,
+                        "top-bottom" => StereoLayout::TopBottom,
+                        _ => return Err(params.invalid(column, "layout", token, "expected 'side-by-side' or 'top-bottom'")),
+                    }
+                };
+                self.stereo = Some(Stereo { interocular_distance, convergence_distance, layout });
+            }
+            "rnd" => {
+                self.seed = Some(params.u64("seed")?);
+            }
+            "smp" => {
+                let (column, token) = params.token("sampler")?;
+                let sampler = token.parse().map_err(|_| {
+                    params.invalid(column, "sampler", token, "expected 'independent', 'stratified', 'halton' or 'sobol'")
+                })?;
+                self.sampler = Some(sampler);
+            }
+            "ada" => {
+                let noise_threshold = params.positive_f64("noise_threshold")?;
+                let min_n = if params.is_empty() { 2 } else { params.super_sampling_n("min_super_sampling_n")? };
+                self.adaptive_sampling = Some(AdaptiveSampling { min_samples: min_n * min_n, noise_threshold });
+            }
+            "flt" => {
+                let (column, token) = params.token("filter")?;
+                let kind: FilterKind = token.parse().map_err(|_| {
+                    params.invalid(column, "filter", token, "expected 'box', 'tent', 'gaussian', 'mitchell' or 'lanczos'")
+                })?;
+                self.filter = Some(if params.is_empty() {
+                    Filter::with_default_radius(kind)
+                } else {
+                    Filter::new(kind, params.positive_f64("radius")?)
+                });
+            }
+            "til" => {
+                self.tile_size = Some(params.positive_u32("tile_size")?);
+            }
+            "res" => {
+                let location = params.location(params.tokens.get(params.next_token).map_or(params.end_column, |&(column, _)| column));
+                self.resolution = Some((
+                    params.positive_u32("image_width")?,
+                    params.positive_u32("image_height")?,
+                    location,
+                ));
+            }
+            "set" => {
+                self.seen_settings = true;
+                self.settings = Some((
+                    params.color("background_color")?,
+                    params.positive_u32("shadow_rays_n")?,
+                    params.u32("max_recursion")?,
+                    params.super_sampling_n("super_sampling_n")?,
+                ));
+            }
+            "mtl" => {
+                let material = parse_material(params);
+                // Keep the material numbering of later lines intact even if this one is invalid
+                self.materials.push(*material.as_ref().unwrap_or(&Material::default()));
+                material?;
+            }
+            "sph" => {
+                self.objects.push(Box::new(Sphere {
+                    center: params.vector("center")?,
+                    radius: params.positive_f64("radius")?,
+                    material: params.material(&self.materials)?,
+                }));
+            }
+            "pln" => {
+                self.objects.push(Box::new(Plane {
+                    normal: params.vector("normal")?,
+                    offset: params.f64("offset")?,
+                    material: params.material(&self.materials)?,
+                }));
+            }
+            "trg" => {
+                let v1 = params.vector("vertex_1")?;
+                let v2 = params.vector("vertex_2")?;
+                let v3 = params.vector("vertex_3")?;
+                // Optionally followed by per-vertex normals for smooth shading
+                if params.remaining() > 1 {
+                    let vertex_normals = [
+                        params.nonzero_vector("normal_1")?,
+                        params.nonzero_vector("normal_2")?,
+                        params.nonzero_vector("normal_3")?,
+                    ];
+                    self.objects.push(Box::new(Triangle::new_smooth(
+                        v1, v2, v3, vertex_normals, params.material(&self.materials)?)));
+                } else {
+                    self.objects.push(Box::new(Triangle::new(v1, v2, v3, params.material(&self.materials)?)));
+                }
+            }
+            "box" => {
+                let corner1 = params.vector("min_corner")?;
+                let corner2 = params.opposite_corner("max_corner", &corner1)?;
+                let material = params.material(&self.materials)?;
+                let rotation = if params.is_empty() { Vector::new(0.0, 0.0, 0.0) } else { params.vector("rotation")? };
+                self.objects.push(Box::new(Cuboid::new(corner1, corner2, rotation, material)));
+            }
+            "cyl" => {
+                let base = params.vector("base")?;
+                let top = params.distinct_vector("top", &base)?;
+                let radius = params.positive_f64("radius")?;
+                let material = params.material(&self.materials)?;
+                let capped = parse_capped(params)?;
+                self.objects.push(Box::new(Cylinder::new(base, top, radius, capped, material)));
+            }
+            "con" => {
+                let apex = params.vector("apex")?;
+                let base = params.distinct_vector("base", &apex)?;
+                let base_radius = params.positive_f64("base_radius")?;
+                let material = params.material(&self.materials)?;
+                let capped = parse_capped(params)?;
+                self.objects.push(Box::new(Cone::new(apex, base, base_radius, capped, material)));
+            }
+            "dsk" => {
+                self.objects.push(Box::new(Disk::new(
+                    params.vector("center")?,
+                    params.nonzero_vector("normal")?,
+                    params.positive_f64("radius")?,
+                    params.material(&self.materials)?,
+                )));
+            }
+            "tor" => {
+                let center = params.vector("center")?;
+                let axis = params.nonzero_vector("axis")?;
+                let major_radius = params.positive_f64("major_radius")?;
+                let minor_radius = params.positive_f64("minor_radius")?;
+                // Only ring tori have a hole, horn and spindle tori overlap themselves
+                if minor_radius >= major_radius {
+                    let (column, token) = params.tokens[params.next_token - 1];
+                    return Err(params.invalid(column, "minor_radius", token, "expected a number smaller than the major radius"));
+                }
+                self.objects.push(Box::new(Torus::new(center, axis, major_radius, minor_radius, params.material(&self.materials)?)));
+            }
+            "qad" => {
+                let (corner, edge1, edge2) = parse_quad(params)?;
+                self.objects.push(Box::new(Quad::new(corner, edge1, edge2, params.material(&self.materials)?)));
+            }
+            "csg" => {
+                let (column, token) = params.token("operation")?;
+                let operation = token.parse().map_err(|_| {
+                    params.invalid(column, "operation", token, "expected 'union', 'intersection' or 'difference'")
+                })?;
+                self.open_csgs.push(OpenCsg {
+                    operation,
+                    location: params.location(params.item_column),
+                    first_operand: self.objects.len(),
+                });
+            }
+            "obj" => {
+                let (column, path) = params.token("path")?;
+                // Relative mesh paths are relative to the scene file
+                let path = params.file_path.parent().unwrap_or_else(|| Path::new("")).join(path);
+                let material = params.material(&self.materials)?;
+                let mut transform = Transform::identity();
+                if !params.is_empty() {
+                    let translation = params.vector("translation")?;
+                    let scale = if params.is_empty() { 1.0 } else { params.positive_f64("scale")? };
+                    let rotation = if params.is_empty() { Vector::new(0.0, 0.0, 0.0) } else { params.vector("rotation")? };
+                    transform = Transform::new(translation, scale, rotation);
+                }
+                let mut mesh = obj_loader::load_obj(&path, material).map_err(|error| match error {
+                    ObjError::Io(error) => SceneParseError::MeshIo { location: params.location(column), path: path.clone(), error },
+                    error => SceneParseError::InvalidMesh { location: params.location(column), path: path.clone(), error },
+                })?;
+                mesh.transform(&transform);
+                self.objects.extend(mesh.into_objects());
+            }
+            "lgt" => {
+                self.lights.push(Light::new(
+                    params.vector("position")?,
+                    params.color("color")?,
+                    params.f64("specular_intensity")?,
+                    params.unit_f64("shadow_intensity")?,
+                    // Zero for a point light
+                    params.non_negative_f64("radius")?,
+                ));
+            }
+            "qlt" => {
+                let (corner, edge1, edge2) = parse_quad(params)?;
+                self.lights.push(Light::new_quad(
+                    Quad::new(corner, edge1, edge2, Material::default()),
+                    params.color("color")?,
+                    params.f64("specular_intensity")?,
+                    params.unit_f64("shadow_intensity")?,
+                ));
+            }
+            _ => {
+                return Err(SceneParseError::UnknownItem {
+                    location: params.location(params.item_column),
+                    item: params.item.to_string(),
+                });
+            }
+        }
+        self.close_csgs(params, first_new_object)
+    }
+
+    /// Replaces the operands of the innermost 'csg' items with their CSG nodes, once both have been parsed.
+    /// Objects from `first_new_object` on were added by the current line, they are dropped if they can't be operands.
+    fn close_csgs(&mut self, params: &Params, first_new_object: usize) -> Result<(), SceneParseError> {
+        if !self.open_csgs.is_empty() && self.objects[first_new_object..].iter().any(|object| !object.is_closed()) {
+            self.objects.truncate(first_new_object);
+            return Err(params.invalid(params.item_column, "csg_operand", params.item,
+                "expected a closed object: 'sph', 'pln', 'box', 'tor', 'csg', or a capped 'cyl' or 'con'"));
+        }
+        while let Some(&OpenCsg { operation, first_operand, .. }) = self.open_csgs.last() {
+            if self.objects.len() < first_operand + 2 {
+                break;
+            }
+            let right = self.objects.pop().unwrap();
+            let left = self.objects.pop().unwrap();
+            self.open_csgs.pop();
+            self.objects.push(Box::new(Csg::new(operation, left, right)));
+        }
+        Ok(())
+    }
+}
+
+/// "corner edge1 edge2"
+fn parse_quad(params: &mut Params) -> Result<(Vector, Vector, Vector), SceneParseError> {
+    let corner = params.vector("corner")?;
+    let edge1 = params.nonzero_vector("edge_1")?;
+    let column = params.tokens.get(params.next_token).map_or(params.end_column, |&(column, _)| column);
+    let edge2 = params.vector("edge_2")?;
+    if (edge1 ^ edge2).norm_squared() == 0.0 {
+        let token = params.tokens[params.next_token - 3].1;
+        return Err(params.invalid(column, "edge_2", token, "expected an edge that isn't parallel to the first"));
+    }
+    Ok((corner, edge1, edge2))
+}
+
+/// Optional "open" (no caps) or "capped", the default
+fn parse_capped(params: &mut Params) -> Result<bool, SceneParseError> {
+    if params.is_empty() {
+        return Ok(true);
+    }
+    let (column, token) = params.token("caps")?;
+    match token {
+        "capped" => Ok(true),
+        "open" => Ok(false),
+        _ => Err(params.invalid(column, "caps", token, "expected 'capped' or 'open'")),
+    }
+}
+
+/// "sc_dist sc_width", or one of "hfov degrees", "vfov degrees" and "lens mm" (35mm equivalent focal length)
+fn parse_field_of_view(params: &mut Params) -> Result<FieldOfView, SceneParseError> {
+    let keyword = params.peek();
+    match keyword {
+        Some("hfov") | Some("vfov") => {
+            params.token("field_of_view")?;
+            let (column, token, degrees) = params.number("fov_degrees")?;
+            if degrees <= 0.0 || degrees >= 180.0 {
+                return Err(params.invalid(column, "fov_degrees", token, "expected an angle between 0 and 180 degrees"));
+            }
+            if keyword == Some("hfov") {
+                Ok(FieldOfView::Horizontal(degrees))
+            } else {
+                Ok(FieldOfView::Vertical(degrees))
+            }
+        }
+        Some("lens") => {
+            params.token("field_of_view")?;
+            Ok(FieldOfView::FocalLength35mm(params.positive_f64("focal_length_mm")?))
+        }
+        _ => Ok(FieldOfView::Screen {
+            distance: params.positive_f64("screen_distance")?,
+            width: params.positive_f64("screen_width")?,
+        }),
+    }
+}
+
+fn parse_projection(params: &mut Params) -> Result<Projection, SceneParseError> {
+    let (column, token) = params.token("projection")?;
+    match token {
+        "perspective" => Ok(Projection::Perspective),
+        "orthographic" => Ok(Projection::Orthographic),
+        "equirectangular" => Ok(Projection::Equirectangular),
+        "cubemap" => Ok(Projection::CubeMap),
+        "equidistant" | "equisolid" => {
+            let mapping = if token == "equidistant" { FisheyeMapping::Equidistant } else { FisheyeMapping::Equisolid };
+            let (column, token, field_of_view) = params.number("fov_degrees")?;
+            if field_of_view <= 0.0 || field_of_view > 360.0 {
+                return Err(params.invalid(column, "fov_degrees", token, "expected an angle between 0 and 360 degrees"));
+            }
+            Ok(Projection::Fisheye { mapping, field_of_view })
+        }
+        _ => Err(params.invalid(column, "projection", token,
+                                "expected 'perspective', 'orthographic', 'equirectangular', 'cubemap', 'equidistant' or 'equisolid'")),
+    }
+}
+
+fn parse_material(params: &mut Params) -> Result<Material, SceneParseError> {
+    Ok(Material::new(
+        params.color("diffuse_color")?,
+        params.color("specular_color")?,
+        params.color("reflection_color")?,
+        params.f64("phong_specularity")?,
+        params.unit_f64("transparency")?,
+        if params.is_empty() { 1.0 } else { params.positive_f64("refractive_index")? },
+    ))
+}
+
+/// Cursor over the whitespace separated tokens of a single scene line
+struct Params<'a> {
+    file_path: &'a Path,
+    line_number: usize,
+    item: &'a str,
+    item_column: usize,
+    tokens: Vec<(usize, &'a str)>,
+    next_token: usize,
+    end_column: usize,
+}
+
+impl<'a> Params<'a> {
+    fn new(file_path: &'a Path, line_number: usize, line: &'a str) -> Params<'a> {
+        let mut tokens = Vec::new();
+        let mut token_start: Option<usize> = None;
+        for (index, c) in line.char_indices() {
+            match (c.is_whitespace(), token_start) {
+                (true, Some(start)) => {
+                    tokens.push((start, &line[start..index]));
+                    token_start = None;
+                }
+                (false, None) => token_start = Some(index),
+                _ => {}
+            }
+        }
+        if let Some(start) = token_start {
+            tokens.push((start, &line[start..]));
+        }
+        // Columns are counted in characters, starting at 1
+        let tokens: Vec<(usize, &str)> = tokens.into_iter()
+            .map(|(start, token)| (line[..start].chars().count() + 1, token))
+            .collect();
+        let (item_column, item) = tokens[0]; //Note: this can't fail because line is not empty
+        Params {
+            file_path,
+            line_number,
+            item,
+            item_column,
+            tokens,
+            next_token: 1,
+            end_column: line.chars().count() + 1,
+        }
+    }
+
+    fn location(&self, column: usize) -> Location {
+        Location {
+            file: self.file_path.to_path_buf(),
+            line: self.line_number,
+            column,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.next_token >= self.tokens.len()
+    }
+
+    fn remaining(&self) -> usize {
+        self.tokens.len().saturating_sub(self.next_token)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.next_token).map(|&(_, token)| token)
+    }
+
+    fn token(&mut self, param: &'static str) -> Result<(usize, &'a str), SceneParseError> {
+        if self.is_empty() {
+            return Err(SceneParseError::MissingParam {
+                location: self.location(self.end_column),
+                item: self.item.to_string(),
+                param,
+            });
+        }
+        let token = self.tokens[self.next_token];
+        self.next_token += 1;
+        Ok(token)
+    }
+
+    fn invalid(&self, column: usize, param: &'static str, token: &str, reason: &'static str) -> SceneParseError {
+        SceneParseError::InvalidParam {
+            location: self.location(column),
+            param,
+            token: token.to_string(),
+            reason,
+        }
+    }
+
+    fn number(&mut self, param: &'static str) -> Result<(usize, &'a str, f64), SceneParseError> {
+        let (column, token) = self.token(param)?;
+        match token.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok((column, token, value)),
+            _ => Err(self.invalid(column, param, token, "expected a number")),
+        }
+    }
+
+    fn f64(&mut self, param: &'static str) -> Result<f64, SceneParseError> {
+        self.number(param).map(|(_, _, value)| value)
+    }
+
+    fn unit_f64(&mut self, param: &'static str) -> Result<f64, SceneParseError> {
+        let (column, token, value) = self.number(param)?;
+        if !(0.0..=1.0).contains(&value) {
+            return Err(self.invalid(column, param, token, "expected a number between 0 and 1"));
+        }
+        Ok(value)
+    }
+
+    fn positive_f64(&mut self, param: &'static str) -> Result<f64, SceneParseError> {
+        let (column, token, value) = self.number(param)?;
+        if value <= 0.0 {
+            return Err(self.invalid(column, param, token, "expected a positive number"));
+        }
+        Ok(value)
+    }
+
+    fn non_negative_f64(&mut self, param: &'static str) -> Result<f64, SceneParseError> {
+        let (column, token, value) = self.number(param)?;
+        if value < 0.0 {
+            return Err(self.invalid(column, param, token, "expected a non-negative number"));
+        }
+        Ok(value)
+    }
+
+    fn u32(&mut self, param: &'static str) -> Result<u32, SceneParseError> {
+        let (column, token) = self.token(param)?;
+        token.parse().map_err(|_| self.invalid(column, param, token, "expected an unsigned integer"))
+    }
+
+    fn u64(&mut self, param: &'static str) -> Result<u64, SceneParseError> {
+        let (column, token) = self.token(param)?;
+        token.parse().map_err(|_| self.invalid(column, param, token, "expected an unsigned integer"))
+    }
+
+    fn positive_u32(&mut self, param: &'static str) -> Result<u32, SceneParseError> {
+        let (column, token) = self.token(param)?;
+        match token.parse::<u32>() {
+            Ok(value) if value > 0 => Ok(value),
+            _ => Err(self.invalid(column, param, token, "expected a positive integer")),
+        }
+    }
+
+    /// N of N x N samples per pixel, small enough for the sample count not to overflow
+    fn super_sampling_n(&mut self, param: &'static str) -> Result<u32, SceneParseError> {
+        let (column, token) = self.token(param)?;
+        match token.parse::<u32>() {
+            Ok(value) if value > 0 && value <= MAX_SUPER_SAMPLING_N => Ok(value),
+            _ => Err(self.invalid(column, param, token, "expected a positive integer up to 65535")),
+        }
+    }
+
+    fn vector(&mut self, param: &'static str) -> Result<Vector, SceneParseError> {
+        Ok(Vector::new(self.f64(param)?, self.f64(param)?, self.f64(param)?))
+    }
+
+    fn nonzero_vector(&mut self, param: &'static str) -> Result<Vector, SceneParseError> {
+        let column = self.tokens.get(self.next_token).map_or(self.end_column, |&(column, _)| column);
+        let vector = self.vector(param)?;
+        if vector.norm_squared() == 0.0 {
+            let token = self.tokens[self.next_token - 3].1;
+            return Err(self.invalid(column, param, token, "expected a non-zero vector"));
+        }
+        Ok(vector)
+    }
+
+    /// A point that isn't `other`, e.g. the second end of an axis
+    fn distinct_vector(&mut self, param: &'static str, other: &Vector) -> Result<Vector, SceneParseError> {
+        let column = self.tokens.get(self.next_token).map_or(self.end_column, |&(column, _)| column);
+        let vector = self.vector(param)?;
+        if vector == *other {
+            let token = self.tokens[self.next_token - 3].1;
+            return Err(self.invalid(column, param, token, "expected a point different from the previous one"));
+        }
+        Ok(vector)
+    }
+
+    /// Parses a point that differs from `other` on every axis, so the box between them isn't flat
+    fn opposite_corner(&mut self, param: &'static str, other: &Vector) -> Result<Vector, SceneParseError> {
+        let first_token = self.next_token;
+        let corner = self.vector(param)?;
+        let axes = [(corner.x, other.x), (corner.y, other.y), (corner.z, other.z)];
+        if let Some(axis) = axes.iter().position(|&(a, b)| a == b) {
+            let (column, token) = self.tokens[first_token + axis];
+            return Err(self.invalid(column, param, token, "expected a coordinate different from the other corner's"));
+        }
+        Ok(corner)
+    }
+
+    fn color(&mut self, param: &'static str) -> Result<Color, SceneParseError> {
+        Ok(Color::new(self.f64(param)?, self.f64(param)?, self.f64(param)?))
+    }
+
+    /// Parses a 1-based index into the materials defined so far
+    fn material(&mut self, materials: &[Material]) -> Result<Material, SceneParseError> {
+        let (column, token) = self.token("material_index")?;
+        match token.parse::<usize>() {
+            Ok(index) if index >= 1 && index <= materials.len() => Ok(materials[index - 1]),
+            _ => Err(SceneParseError::InvalidMaterialIndex {
+                location: self.location(column),
+                token: token.to_string(),
+                material_count: materials.len(),
+            }),
+        }
+    }
+
+    /// Checks that all of the line's tokens were consumed
+    fn finish(&self) -> Result<(), SceneParseError> {
+        match self.tokens.get(self.next_token) {
+            Some(&(column, token)) => Err(SceneParseError::UnexpectedParam {
+                location: self.location(column),
+                item: self.item.to_string(),
+                token: token.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::almost_eq;
+    use ray::Ray;
+
+    /// Parses `items`, starting on line 4, after a camera, the settings and a material
+    fn parse_with_header(items: &str) -> Result<Scene, Vec<SceneParseError>> {
+        let text = format!("cam 0 0 0 0 0 1 0 1 0 1 1\nset 1 1 1 1 10 1\nmtl 1 1 1 0 0 0 0 0 0 1 0\n{}\n", items);
+        parse_scene(text.as_bytes(), Path::new("test.txt"))
+    }
+
+    /// (line, column) of each error
+    fn error_locations(errors: &[SceneParseError]) -> Vec<(usize, usize)> {
+        errors.iter()
+            .map(|error| {
+                let location = error.location().unwrap();
+                (location.line, location.column)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_all_errors() {
+        let text = "\
+cam 0 0 0 0 0 1 0 1 0 1 1
+set 1 1 1 0 10 1
+mtl 1 1 1 0 0 0 0 0 0 1 2
+sph 0 0 4 1 2
+sph 0 0 4 x 1
+xyz 0 0 0
+pln 0 1 0 -1 1 7
+trg 0 0 0 1 0 0 0 1 0
+";
+        let errors = parse_scene(text.as_bytes(), Path::new("test.txt")).err().expect("Expected errors");
+        assert_eq!(error_locations(&errors), vec![(2, 11), (3, 25), (4, 13), (5, 11), (6, 1), (7, 16), (8, 22)]);
+        match errors[1] {
+            SceneParseError::InvalidParam { param, ref token, .. } => {
+                assert_eq!(param, "transparency");
+                assert_eq!(token, "2");
+            }
+            ref error => panic!("Unexpected error {}", error),
+        }
+        match errors[2] {
+            SceneParseError::InvalidMaterialIndex { material_count, .. } => assert_eq!(material_count, 1),
+            ref error => panic!("Unexpected error {}", error),
+        }
+    }
+
+    #[test]
+    fn test_resolution() {
+        let text = "\
+res 640 480
+cam 0 0 0 0 0 1 0 1 0 1 1
+set 1 1 1 1 10 1
+";
+        let scene = parse_scene(text.as_bytes(), Path::new("test.txt")).expect("Could not parse scene");
+        assert_eq!((scene.camera.image_width, scene.camera.image_height), (640, 480));
+        assert_eq!(scene.camera.screen_height, 0.75);
+    }
+
+    #[test]
+    fn test_camera_field_of_view() {
+        let parse_camera = |cam: &str| {
+            let text = format!("{}\nres 300 200\nset 1 1 1 1 10 1\n", cam);
+            parse_scene(text.as_bytes(), Path::new("test.txt")).map(|scene| scene.camera)
+        };
+        let camera = parse_camera("cam 0 0 0 0 0 1 0 1 0 vfov 90").expect("Could not parse scene");
+        assert_eq!(camera.field_of_view, FieldOfView::Vertical(90.0));
+        assert!(almost_eq(camera.screen_height / camera.screen_distance, 2.0));
+        let camera = parse_camera("cam 0 0 0 0 0 1 0 1 0 lens 50 0.1 2").expect("Could not parse scene");
+        assert_eq!(camera.field_of_view, FieldOfView::FocalLength35mm(50.0));
+        assert_eq!(camera.focal_distance, 2.0);
+        assert!(parse_camera("cam 0 0 0 0 0 1 0 1 0 hfov 180").is_err());
+        assert!(parse_camera("cam 0 0 0 0 0 1 0 1 0 fov 60").is_err());
+        // Degenerate views
+        assert!(parse_camera("cam 0 0 1 0 0 1 0 1 0 1 1").is_err());
+        assert!(parse_camera("cam 0 0 0 0 0 1 0 0 -2 1 1").is_err());
+        assert!(parse_camera("cam 0 0 0 0 0 1 0 0 0 1 1").is_err());
+    }
+
+    #[test]
+    fn test_radii() {
+        assert!(parse_with_header("sph 0 0 4 -1 1").is_err());
+        assert!(parse_with_header("sph 0 0 4 0 1").is_err());
+        assert!(parse_with_header("lgt 0 5 0 1 1 1 1 1 -1").is_err());
+        // A point light
+        assert!(parse_with_header("lgt 0 5 0 1 1 1 1 1 0").is_ok());
+        assert!(parse_with_header("tor 0 0 4 0 1 0 1 0.25 1").is_ok());
+        match parse_with_header("tor 0 0 4 0 1 0 1 1 1").err().expect("Expected errors")[0] {
+            SceneParseError::InvalidParam { param, ref location, .. } => {
+                assert_eq!(param, "minor_radius");
+                assert_eq!((location.line, location.column), (4, 19));
+            }
+            ref error => panic!("Unexpected error {}", error),
+        }
+        assert!(parse_with_header("tor 0 0 4 0 1 0 1 2 1").is_err());
+    }
+
+    #[test]
+    fn test_box() {
+        assert!(parse_with_header("box -1 -1 3 1 1 5 1").is_ok());
+        // The corners can be given in any order
+        assert!(parse_with_header("box 1 1 5 -1 -1 3 1 0 45 0").is_ok());
+        // Flat along y
+        match parse_with_header("box -1 0 3 1 0 5 1").err().expect("Expected errors")[0] {
+            SceneParseError::InvalidParam { param, ref location, .. } => {
+                assert_eq!(param, "max_corner");
+                assert_eq!((location.line, location.column), (4, 14));
+            }
+            ref error => panic!("Unexpected error {}", error),
+        }
+        assert!(parse_with_header("box 0 0 0 0 0 0 1").is_err());
+    }
+
+    #[test]
+    fn test_projection() {
+        let scene = parse_with_header("prj equisolid 180\ndst -0.2 0.05 0 0").expect("Could not parse scene");
+        assert_eq!(scene.camera.projection, Projection::Fisheye { mapping: FisheyeMapping::Equisolid, field_of_view: 180.0 });
+        assert_eq!(scene.camera.distortion, Some(LensDistortion { k1: -0.2, k2: 0.05, p1: 0.0, p2: 0.0, k3: 0.0 }));
+
+        let parse_projection = |prj: &str| parse_with_header(prj).map(|scene| scene.camera.projection);
+        assert_eq!(parse_projection("prj equidistant 360").expect("Could not parse scene"),
+                   Projection::Fisheye { mapping: FisheyeMapping::Equidistant, field_of_view: 360.0 });
+        for prj in &["prj equidistant 0", "prj equisolid -90", "prj equisolid 361", "prj equidistant", "prj fisheye 180"] {
+            match parse_projection(prj).expect_err("Expected errors")[0] {
+                SceneParseError::InvalidParam { ref location, .. } | SceneParseError::MissingParam { ref location, .. } => {
+                    assert_eq!(location.line, 4);
+                }
+                ref error => panic!("Unexpected error {}", error),
+            }
+        }
+        let parse_distortion = |dst: &str| parse_with_header(dst).map(|scene| scene.camera.distortion);
+        assert_eq!(parse_distortion("dst 0.1 0 0.01 0 0.002").expect("Could not parse scene"),
+                   Some(LensDistortion { k1: 0.1, k2: 0.0, p1: 0.01, p2: 0.0, k3: 0.002 }));
+        assert!(parse_distortion("dst 0.1 0 0").is_err());
+        assert!(parse_distortion("dst 0.1 0 0 0 0 0").is_err());
+
+        // Cube maps need six square faces per view, which is the default resolution for them
+        let parse_cube_map = |items: &str| {
+            parse_with_header(&format!("prj cubemap\n{}", items)).map(|scene| (scene.camera.image_width, scene.camera.image_height))
+        };
+        assert_eq!(parse_cube_map("").expect("Could not parse scene"), (3000, 500));
+        assert_eq!(parse_cube_map("ste 0.1 5 top-bottom\n").expect("Could not parse scene"), (3000, 1000));
+        assert_eq!(parse_cube_map("res 600 100\n").expect("Could not parse scene"), (600, 100));
+        match parse_cube_map("res 400 200\n").expect_err("Expected errors")[0] {
+            SceneParseError::InvalidParam { param, ref location, .. } => {
+                assert_eq!(param, "image_width");
+                assert_eq!((location.line, location.column), (5, 5));
+            }
+            ref error => panic!("Unexpected error {}", error),
+        }
+
+        // Orthographic rays start on the screen, one unit ahead here, so the focal plane must be beyond it
+        let parse_orthographic = |cam: &str| {
+            let text = format!("{}\nset 1 1 1 1 10 1\nprj orthographic\n", cam);
+            parse_scene(text.as_bytes(), Path::new("test.txt")).map(|scene| scene.camera.focal_distance)
+        };
+        assert_eq!(parse_orthographic("cam 0 0 0 0 0 1 0 1 0 1 1 0.1 3").expect("Could not parse scene"), 3.0);
+        match parse_orthographic("cam 0 0 0 0 0 1 0 1 0 1 1 0.1 0.5").expect_err("Expected errors")[0] {
+            SceneParseError::InvalidParam { param, ref location, .. } => {
+                assert_eq!(param, "focal_distance");
+                assert_eq!((location.line, location.column), (1, 31));
+            }
+            ref error => panic!("Unexpected error {}", error),
+        }
+        assert!(parse_orthographic("cam 0 0 0 0 0 1 0 1 0 1 1 0.1 0 0 1").is_err());
+    }
+
+    #[test]
+    fn test_sampling() {
+        assert_eq!(parse_with_header("").expect("Could not parse scene").seed, 0);
+        assert_eq!(parse_with_header("rnd 42\n").expect("Could not parse scene").seed, 42);
+        assert!(parse_with_header("rnd -1\n").is_err());
+        assert!(parse_with_header("rnd 0.5\n").is_err());
+        assert_eq!(parse_with_header("").expect("Could not parse scene").sampler, SamplerKind::Stratified);
+        assert_eq!(parse_with_header("smp halton\n").expect("Could not parse scene").sampler, SamplerKind::Halton);
+        assert!(parse_with_header("smp random\n").is_err());
+        assert_eq!(parse_with_header("").expect("Could not parse scene").adaptive_sampling, None);
+        assert_eq!(parse_with_header("ada 0.01\n").expect("Could not parse scene").adaptive_sampling,
+                   Some(AdaptiveSampling { min_samples: 4, noise_threshold: 0.01 }));
+        assert_eq!(parse_with_header("ada 0.01 3\n").expect("Could not parse scene").adaptive_sampling,
+                   Some(AdaptiveSampling { min_samples: 9, noise_threshold: 0.01 }));
+        assert!(parse_with_header("ada 0 2\n").is_err());
+        assert!(parse_with_header("ada 0.01 65536\n").is_err());
+        assert_eq!(parse_with_header("").expect("Could not parse scene").filter, Filter::default());
+        assert_eq!(parse_with_header("flt gaussian\n").expect("Could not parse scene").filter, Filter::new(FilterKind::Gaussian, 1.5));
+        assert_eq!(parse_with_header("flt tent 2\n").expect("Could not parse scene").filter, Filter::new(FilterKind::Tent, 2.0));
+        assert!(parse_with_header("flt sinc\n").is_err());
+        assert!(parse_with_header("flt box 0\n").is_err());
+    }
+
+    #[test]
+    fn test_csg() {
+        // Operands can be CSG nodes themselves, and other items may come in between
+        let scene = parse_with_header("\
+csg difference
+csg union
+sph 0 0 5 1 1
+box 0 0 4 1 1 6 1
+lgt 0 5 0 1 1 1 1 1 0
+cyl 0 -2 5 0 2 5 0.5 1
+sph 3 0 5 1 1
+").expect("Could not parse scene");
+        assert_eq!(scene.objects.len(), 2);
+        // From within the hole of the cylinder
+        let hit = scene.objects.find_closest_hit(&Ray::new(Vector::new(0.0, 0.0, 4.7), Vector::new(0.0, 0.0, 1.0)));
+        assert!(almost_eq(hit.expect("Expected a hit").distance, 0.8));
+
+        let errors = parse_with_header("csg union\nsph 0 0 5 1 1\ncyl 0 -2 5 0 2 5 0.5 1 open\ncsg intersection\n").err().expect("Expected errors");
+        assert_eq!(error_locations(&errors), vec![(6, 1), (4, 1), (7, 1)]);
+        match errors[0] {
+            SceneParseError::InvalidParam { param, .. } => assert_eq!(param, "csg_operand"),
+            ref error => panic!("Unexpected error {}", error),
+        }
+
+        // The rejected operand is dropped, so the next object takes its place and the lines after it are fine
+        let errors = parse_with_header("csg union\nsph 0 0 5 1 1\ncyl 0 -2 5 0 2 5 0.5 1 open\nlgt 0 5 0 1 1 1 1 1 0\nsph 3 0 5 1 1\nres 40 30\n")
+            .err().expect("Expected errors");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location().map(|location| location.line), Some(6));
+    }
+
+    #[test]
+    fn test_missing_items() {
+        let errors = parse_scene("mtl 1 1 1 0 0 0 0 0 0 1 0\n".as_bytes(), Path::new("test.txt")).err().expect("Expected errors");
+        let missing_items: Vec<&str> = errors.iter()
+            .map(|error| match *error {
+                SceneParseError::MissingItem { item, .. } => item,
+                ref error => panic!("Unexpected error {}", error),
+            })
+            .collect();
+        assert_eq!(missing_items, vec!["cam", "set"]);
+    }
+
+    #[test]
+    fn test_missing_mesh_file() {
+        let errors = parse_with_header("obj missing.obj 1").err().expect("Expected errors");
+        match errors[0] {
+            SceneParseError::MeshIo { ref location, .. } => assert_eq!((location.line, location.column), (4, 5)),
+            ref error => panic!("Unexpected error {}", error),
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use model_object::ModelObject;
 use vector::Vector;
 use ray::Ray;
-use hit::Hit;
+use hit::{self, Hit, Interval};
 use material::Material;
+use bounding_box::BoundingBox;
 use std::option::Option::{None, Some};
 
 #[derive(Debug, Copy, Clone)]
@@ -12,33 +13,60 @@ pub struct Sphere {
     pub radius: f64
 }
 
-impl ModelObject for Sphere {
-    fn material(&self) -> Material {
-        self.material
-    }
-
-    fn try_hit(&self, ray: &Ray) -> Option<Hit> {
+impl Sphere {
+    /// Distances along the ray's whole line to where it enters and leaves the sphere
+    fn line_intersections(&self, ray: &Ray) -> Option<(f64, f64)> {
         // Geometric method
         let el = self.center - ray.position;
         let t_ca = el % ray.direction;
-        if t_ca < 0.0 {
-            return None;
-        }
         let d_square = el.norm_squared() - (t_ca * t_ca);
         let r_square = self.radius * self.radius;
         if d_square > r_square {
             return None;
         }
         let t_hc = (r_square - d_square).sqrt();
-        let distance_near = t_ca - t_hc;
-        //TODO: emit distance_far ?
-        //let distance_far = t_ca + t_hc;
-        if distance_near < 0.0 {
+        Some((t_ca - t_hc, t_ca + t_hc))
+    }
+}
+
+impl ModelObject for Sphere {
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let (distance_near, distance_far) = self.line_intersections(ray)?;
+        // If the ray starts inside the sphere, the hit is where it leaves
+        let distance = if distance_near >= 0.0 {
+            distance_near
+        } else if distance_far >= 0.0 {
+            distance_far
+        } else {
             return None;
-        }
-        let hit_point = ray.position + ray.direction * distance_near;
+        };
+        let hit_point = ray.position + ray.direction * distance;
         let hit_normal = self.center.direction_to(&hit_point);
-        let hit = Hit::new(ray, distance_near, hit_normal, hit_point, self);
+        let hit = Hit::new(ray, distance, hit_normal, hit_point, self);
         Some(hit)
     }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let r = Vector::new(self.radius, self.radius, self.radius);
+        Some(BoundingBox::new(self.center - r, self.center + r))
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<Interval<'_>>> {
+        let crossings = match self.line_intersections(ray) {
+            Some((distance_near, distance_far)) => vec![distance_near, distance_far],
+            None => Vec::new(),
+        };
+        let crossings = crossings.into_iter()
+            .map(|distance| (distance, self.center.direction_to(&(ray.position + ray.direction * distance))))
+            .collect();
+        Some(hit::intervals_between(self, crossings))
+    }
 }
\ No newline at end of file
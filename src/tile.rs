@@ -0,0 +1,64 @@
+use std::cmp::Ordering;
+
+/// Rectangle of an image's pixels rendered as one unit of work
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tile {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Splits the image into square tiles (smaller at the right and bottom edges), ordered in a
+/// spiral going out from the center of the image, where the interesting parts usually are
+pub fn spiral_tiles(image_width: u32, image_height: u32, tile_size: u32) -> Vec<Tile> {
+    let tiles_x = image_width.div_ceil(tile_size);
+    let tiles_y = image_height.div_ceil(tile_size);
+    let center_x = (tiles_x as f64 - 1.0) / 2.0;
+    let center_y = (tiles_y as f64 - 1.0) / 2.0;
+
+    let mut positions: Vec<(u32, u32)> = (0..tiles_y).flat_map(|y| (0..tiles_x).map(move |x| (x, y))).collect();
+    // Rings of tiles around the center, each going around clockwise (y points down)
+    let spiral_key = |&(x, y): &(u32, u32)| {
+        let dx = x as f64 - center_x;
+        let dy = y as f64 - center_y;
+        (dx.abs().max(dy.abs()), dy.atan2(dx))
+    };
+    positions.sort_by(|a, b| spiral_key(a).partial_cmp(&spiral_key(b)).unwrap_or(Ordering::Equal));
+
+    positions.iter()
+        .map(|&(x, y)| {
+            let left = x * tile_size;
+            let top = y * tile_size;
+            Tile {
+                left,
+                top,
+                width: tile_size.min(image_width - left),
+                height: tile_size.min(image_height - top),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spiral_tiles() {
+        let tiles = spiral_tiles(100, 70, 16);
+        assert_eq!(tiles.len(), 7 * 5);
+        // The first tile covers the center
+        assert_eq!(tiles[0], Tile { left: 48, top: 32, width: 16, height: 16 });
+        // Every pixel is in exactly one tile
+        let mut coverage = vec![0; 100 * 70];
+        for tile in tiles.iter() {
+            for y in tile.top..tile.top + tile.height {
+                for x in tile.left..tile.left + tile.width {
+                    coverage[(y * 100 + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(coverage.iter().all(|&count| count == 1));
+    }
+}
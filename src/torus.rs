@@ -0,0 +1,154 @@
+use model_object::ModelObject;
+use vector::Vector;
+use ray::Ray;
+use hit::{self, Hit, Interval};
+use material::Material;
+use bounding_box::BoundingBox;
+use polynomial;
+use utils;
+use std::f64;
+use std::option::Option::{None, Some};
+
+/// Ring around `axis` through `center`: the points at `minor_radius` from the circle of
+/// `major_radius` around the axis
+#[derive(Debug, Copy, Clone)]
+pub struct Torus {
+    pub material: Material,
+    pub center: Vector,
+    /// Unit length
+    pub axis: Vector,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    /// With `axis`, the torus' own frame
+    u: Vector,
+    w: Vector,
+}
+
+impl Torus {
+    pub fn new(center: Vector, axis: Vector, major_radius: f64, minor_radius: f64, material: Material) -> Torus {
+        let axis = axis.normalized();
+        let helper = if axis.x.abs() < 0.9 { Vector::new(1.0, 0.0, 0.0) } else { Vector::new(0.0, 1.0, 0.0) };
+        let u = (helper ^ axis).normalized();
+        let w = axis ^ u;
+        Torus {
+            material,
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+            u,
+            w,
+        }
+    }
+
+    /// In the torus' own frame, where it is centered at the origin around the y axis
+    fn local_direction(&self, direction: &Vector) -> Vector {
+        Vector::new(*direction % self.u, *direction % self.axis, *direction % self.w)
+    }
+
+    /// Distances along the ray, from `min_distance` on, to where it crosses the surface, in increasing order
+    fn crossings(&self, ray: &Ray, min_distance: f64) -> Vec<f64> {
+        // Start from where the ray enters the bounding sphere, so that the coefficients stay
+        // small for rays from far away
+        let outer_radius = self.major_radius + self.minor_radius;
+        let to_center = ray.position - self.center;
+        let (sphere_near, sphere_far) = match utils::solve_quadratic(
+            1.0,
+            2.0 * (to_center % ray.direction),
+            to_center % to_center - outer_radius * outer_radius,
+        ) {
+            Some(roots) => roots,
+            None => return Vec::new(),
+        };
+        if sphere_far < min_distance {
+            return Vec::new();
+        }
+        let start = sphere_near.max(min_distance);
+        let o = self.local_direction(&(to_center + ray.direction * start));
+        let d = self.local_direction(&ray.direction);
+
+        // |p|^2 + R^2 - r^2 = 2R * (distance of p from the axis), squared, with p = o + t*d
+        let major_squared = self.major_radius * self.major_radius;
+        let e = o % o - major_squared - self.minor_radius * self.minor_radius;
+        let f = o % d;
+        let coefficients = [
+            e * e - 4.0 * major_squared * (self.minor_radius * self.minor_radius - o.y * o.y),
+            4.0 * f * e + 8.0 * major_squared * o.y * d.y,
+            2.0 * e + 4.0 * f * f + 4.0 * major_squared * d.y * d.y,
+            4.0 * f,
+            1.0,
+        ];
+        polynomial::real_roots(&coefficients, 0.0, sphere_far - start).into_iter()
+            .map(|root| start + root)
+            .collect()
+    }
+
+    /// Outward normal at a point on the surface
+    fn normal_at(&self, point: &Vector) -> Vector {
+        // Away from the nearest point of the circle through the middle of the ring
+        let local_point = self.local_direction(&(*point - self.center));
+        let ring_direction = Vector::new(local_point.x, 0.0, local_point.z).normalized();
+        let local_normal = (local_point - ring_direction * self.major_radius).normalized();
+        self.u * local_normal.x + self.axis * local_normal.y + self.w * local_normal.z
+    }
+}
+
+impl ModelObject for Torus {
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let distance = *self.crossings(ray, 0.0).first()?;
+        let hit_point = ray.position + ray.direction * distance;
+        Some(Hit::new(ray, distance, self.normal_at(&hit_point), hit_point, self))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        // The circle through the middle of the ring, widened by the minor radius
+        let a = self.axis;
+        let extent = Vector::new(
+            self.major_radius * (1.0 - a.x * a.x).max(0.0).sqrt() + self.minor_radius,
+            self.major_radius * (1.0 - a.y * a.y).max(0.0).sqrt() + self.minor_radius,
+            self.major_radius * (1.0 - a.z * a.z).max(0.0).sqrt() + self.minor_radius,
+        );
+        Some(BoundingBox::new(self.center - extent, self.center + extent))
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<Interval<'_>>> {
+        let crossings = self.crossings(ray, f64::NEG_INFINITY).into_iter()
+            .map(|distance| (distance, self.normal_at(&(ray.position + ray.direction * distance))))
+            .collect();
+        Some(hit::intervals_between(self, crossings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::almost_eq;
+
+    #[test]
+    fn test_torus_hits() {
+        let torus = Torus::new(Vector::new(0.0, 0.0, 5.0), Vector::new(0.0, 0.0, 1.0), 2.0, 0.5, Material::default());
+        // Through the ring, in its plane
+        let ray = Ray::new(Vector::new(-10.0, 0.0, 5.0), Vector::new(1.0, 0.0, 0.0));
+        let hit = torus.try_hit(&ray).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 7.5));
+        assert!(hit.hit_normal.almost_equal_to(&Vector::new(-1.0, 0.0, 0.0), utils::EPSILON));
+        // Starting in the hole, the next hit is the inner side
+        let hit = torus.try_hit(&Ray::new(Vector::new(0.0, 0.0, 5.0), Vector::new(1.0, 0.0, 0.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 1.5));
+        assert!(hit.hit_normal.almost_equal_to(&Vector::new(-1.0, 0.0, 0.0), utils::EPSILON));
+        // Down the axis, through the hole
+        assert!(torus.try_hit(&Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))).is_none());
+        // Onto the top of the ring
+        let hit = torus.try_hit(&Ray::new(Vector::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, 1.0))).expect("Expected a hit");
+        assert!(almost_eq(hit.distance, 4.5));
+        assert!(hit.hit_normal.almost_equal_to(&Vector::new(0.0, 0.0, -1.0), utils::EPSILON));
+    }
+}
@@ -0,0 +1,51 @@
+use vector::Vector;
+
+/// Uniform scale, then rotation, then translation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    pub scale: f64,
+    /// Rows of the rotation matrix
+    rotation: [Vector; 3],
+    pub translation: Vector,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform::new(Vector::new(0.0, 0.0, 0.0), 1.0, Vector::new(0.0, 0.0, 0.0))
+    }
+
+    /// `rotation_degrees` are rotations around the x, y and z axes, applied in that order
+    pub fn new(translation: Vector, scale: f64, rotation_degrees: Vector) -> Transform {
+        let (sin_x, cos_x) = rotation_degrees.x.to_radians().sin_cos();
+        let (sin_y, cos_y) = rotation_degrees.y.to_radians().sin_cos();
+        let (sin_z, cos_z) = rotation_degrees.z.to_radians().sin_cos();
+        // Rz * Ry * Rx
+        let rotation = [
+            Vector::new(cos_z * cos_y, cos_z * sin_y * sin_x - sin_z * cos_x, cos_z * sin_y * cos_x + sin_z * sin_x),
+            Vector::new(sin_z * cos_y, sin_z * sin_y * sin_x + cos_z * cos_x, sin_z * sin_y * cos_x - cos_z * sin_x),
+            Vector::new(-sin_y, cos_y * sin_x, cos_y * cos_x),
+        ];
+        Transform {
+            scale,
+            rotation,
+            translation,
+        }
+    }
+
+    pub fn apply_to_point(&self, point: &Vector) -> Vector {
+        self.apply_to_direction(point) * self.scale + self.translation
+    }
+
+    /// Rotates a direction (or normal, as the scale is uniform)
+    pub fn apply_to_direction(&self, direction: &Vector) -> Vector {
+        Vector::new(self.rotation[0] % *direction, self.rotation[1] % *direction, self.rotation[2] % *direction)
+    }
+
+    pub fn invert_point(&self, point: &Vector) -> Vector {
+        self.invert_direction(&(*point - self.translation)) * (1.0 / self.scale)
+    }
+
+    pub fn invert_direction(&self, direction: &Vector) -> Vector {
+        self.rotation[0] * direction.x + self.rotation[1] * direction.y + self.rotation[2] * direction.z
+    }
+}
@@ -3,6 +3,8 @@ use vector::Vector;
 use ray::Ray;
 use hit::Hit;
 use material::Material;
+use bounding_box::BoundingBox;
+use utils::EPSILON;
 use std::option::Option::{None, Some};
 
 #[derive(Debug, Copy, Clone)]
@@ -13,7 +15,32 @@ pub struct Triangle {
     pub v3: Vector,
 
     pub normal: Vector,
-    pub offset: f64
+    /// Per-vertex normals for smooth shading
+    pub vertex_normals: Option<[Vector; 3]>,
+}
+
+/// Where a ray hits a triangle. The barycentric weights of v1, v2, v3 are (1 - u - v, u, v).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TriangleIntersection {
+    pub distance: f64,
+    pub u: f64,
+    pub v: f64,
+}
+
+impl TriangleIntersection {
+    pub fn interpolate(&self, values: &[Vector; 3]) -> Vector {
+        values[0] * (1.0 - self.u - self.v) + values[1] * self.u + values[2] * self.v
+    }
+
+    /// Smooth shading normal from unit vertex normals, None where opposing ones cancel out
+    pub fn interpolate_normal(&self, vertex_normals: &[Vector; 3]) -> Option<Vector> {
+        let normal = self.interpolate(vertex_normals);
+        if normal.norm_squared() < EPSILON * EPSILON {
+            None
+        } else {
+            Some(normal.normalized())
+        }
+    }
 }
 
 impl Triangle {
@@ -27,9 +54,19 @@ impl Triangle {
             v2,
             v3,
             normal,
-            offset: normal % v1,
+            vertex_normals: None,
         }
     }
+
+    /// Triangle shaded by interpolating the given vertex normals.
+    /// The face is oriented so that its normal agrees with them.
+    pub fn new_smooth(v1: Vector, v2: Vector, v3: Vector, vertex_normals: [Vector; 3], material: Material) -> Triangle {
+        let vertex_normals = [vertex_normals[0].normalized(), vertex_normals[1].normalized(), vertex_normals[2].normalized()];
+        let mut triangle = Triangle::new(v1, v2, v3, material);
+        triangle.normal = orient_face_normal(triangle.normal, &vertex_normals);
+        triangle.vertex_normals = Some(vertex_normals);
+        triangle
+    }
 }
 
 impl ModelObject for Triangle {
@@ -37,42 +74,126 @@ impl ModelObject for Triangle {
         self.material
     }
 
-    fn try_hit(&self, ray: &Ray) -> Option<Hit> {
-        let cos_angle = self.normal % ray.direction;
-        if cos_angle == 0.0 {
-            return None;
-        }
-        let t = (self.offset - (ray.position % self.normal)) / cos_angle;
-        if t < 0.0 {
-            return None;
+    fn try_hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let intersection = intersect_triangle(ray, self.v1, self.v2, self.v3)?;
+        let hit_point = ray.position + ray.direction * intersection.distance;
+        let mut hit = Hit::new(ray, intersection.distance, self.normal, hit_point, self);
+        if let Some(normal) = self.vertex_normals.and_then(|ref vertex_normals| intersection.interpolate_normal(vertex_normals)) {
+            hit.set_shading_normal(normal);
         }
+        Some(hit)
+    }
 
-        let hit_point = ray.position + ray.direction * t;
-        if !(check_vec_above_plane(ray.direction, ray.position, self.v1, self.v2, self.v3)
-            && check_vec_above_plane(ray.direction, ray.position, self.v2, self.v3, self.v1)
-            && check_vec_above_plane(ray.direction, ray.position, self.v3, self.v1, self.v2)) {
-            return None;
-        }
-        let hit_normal = if cos_angle > 0.0 {
-            -self.normal
-        } else {
-            self.normal
-        };
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::from_points(vec![self.v1, self.v2, self.v3]))
+    }
+}
+
+/// Flips a face normal if it disagrees with the (average) direction of the vertex normals
+pub fn orient_face_normal(face_normal: Vector, vertex_normals: &[Vector; 3]) -> Vector {
+    if face_normal % (vertex_normals[0] + vertex_normals[1] + vertex_normals[2]) < 0.0 {
+        -face_normal
+    } else {
+        face_normal
+    }
+}
+
+/// Intersects a ray with a triangle (Möller–Trumbore). Hits exactly on edges and vertices count.
+pub fn intersect_triangle(ray: &Ray, v1: Vector, v2: Vector, v3: Vector) -> Option<TriangleIntersection> {
+    let e1 = v2 - v1;
+    let e2 = v3 - v1;
+    let p = ray.direction ^ e2;
+    let determinant = e1 % p;
+    // The ray is parallel to the triangle's plane
+    if determinant == 0.0 {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
 
-        Some(Hit::new(ray, t, hit_normal, hit_point, self))
+    let s = ray.position - v1;
+    let u = (s % p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s ^ e1;
+    let v = (ray.direction % q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = (e2 % q) * inverse_determinant;
+    if distance < 0.0 {
+        return None;
     }
+    Some(TriangleIntersection { distance, u, v })
 }
 
-fn check_vec_above_plane(vec: Vector,
-                         p1: Vector,
-                         p2: Vector,
-                         p3: Vector,
-                         reference_point: Vector) -> bool {
-    let v1 = p2 - p1;
-    let v2 = p3 - p1;
-    let mut n = (v2 ^ v1).normalized();
-    if n % (reference_point - p1) < 0.0 {
-        n *= -1.0;
-    }
-    vec % n >= 0.0
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_triangle() -> (Vector, Vector, Vector) {
+        (Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0))
+    }
+
+    fn intersect_from(position: Vector, direction: Vector) -> Option<TriangleIntersection> {
+        let (v1, v2, v3) = unit_triangle();
+        intersect_triangle(&Ray::new(position, direction.normalized()), v1, v2, v3)
+    }
+
+    #[test]
+    fn test_interior_hit() {
+        let intersection = intersect_from(Vector::new(0.25, 0.5, -2.0), Vector::new(0.0, 0.0, 1.0)).expect("Expected a hit");
+        assert_eq!(intersection, TriangleIntersection { distance: 2.0, u: 0.25, v: 0.5 });
+        let (v1, v2, v3) = unit_triangle();
+        let point = intersection.interpolate(&[v1, v2, v3]);
+        assert!(point.almost_equal_to(&Vector::new(0.25, 0.5, 0.0), ::utils::EPSILON));
+
+        // Hit from behind the face
+        assert!(intersect_from(Vector::new(0.25, 0.5, 2.0), Vector::new(0.0, 0.0, -1.0)).is_some());
+    }
+
+    #[test]
+    fn test_opposing_vertex_normals() {
+        let (v1, v2, v3) = unit_triangle();
+        let away = Vector::new(0.0, 0.0, -1.0);
+        let triangle = Triangle::new_smooth(v1, v2, v3, [away, -away, away], Material::default());
+        // The vertex normals cancel out at weights (0.25, 0.5, 0.25), leaving the face normal
+        let ray = Ray::new(Vector::new(0.5, 0.25, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = triangle.try_hit(&ray).expect("Expected a hit");
+        assert_eq!(hit.hit_normal, away);
+        let ray = Ray::new(Vector::new(0.1, 0.1, -2.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = triangle.try_hit(&ray).expect("Expected a hit");
+        assert!(!hit.hit_normal.x.is_nan() && hit.hit_normal.z < 0.0);
+    }
+
+    #[test]
+    fn test_edge_and_vertex_hits() {
+        let down = Vector::new(0.0, 0.0, 1.0);
+        // Hypotenuse, and the edges along the axes
+        let edge = intersect_from(Vector::new(0.5, 0.5, -1.0), down).expect("Expected a hit on the edge");
+        assert_eq!((edge.u, edge.v), (0.5, 0.5));
+        assert!(intersect_from(Vector::new(0.5, 0.0, -1.0), down).is_some());
+        assert!(intersect_from(Vector::new(0.0, 0.5, -1.0), down).is_some());
+        // Vertices
+        assert!(intersect_from(Vector::new(0.0, 0.0, -1.0), down).is_some());
+        assert!(intersect_from(Vector::new(1.0, 0.0, -1.0), down).is_some());
+        let vertex = intersect_from(Vector::new(0.0, 1.0, -1.0), down).expect("Expected a hit on the vertex");
+        assert_eq!((vertex.u, vertex.v), (0.0, 1.0));
+        // Just outside
+        assert!(intersect_from(Vector::new(0.5, 0.5001, -1.0), down).is_none());
+        assert!(intersect_from(Vector::new(-0.0001, 0.5, -1.0), down).is_none());
+        assert!(intersect_from(Vector::new(0.5, -0.0001, -1.0), down).is_none());
+    }
+
+    #[test]
+    fn test_grazing_and_behind_rays() {
+        // In the triangle's plane
+        assert!(intersect_from(Vector::new(-1.0, 0.25, 0.0), Vector::new(1.0, 0.0, 0.0)).is_none());
+        // Almost parallel to the plane, still hits
+        let grazing = intersect_from(Vector::new(-1.0, 0.25, -0.001), Vector::new(1.0, 0.0, 0.001))
+            .expect("Expected a grazing hit");
+        assert!(::utils::almost_eq(grazing.u, 0.0));
+        // The triangle is behind the ray
+        assert!(intersect_from(Vector::new(0.25, 0.25, 1.0), Vector::new(0.0, 0.0, 1.0)).is_none());
+    }
+}
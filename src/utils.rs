@@ -1,3 +1,4 @@
+#[allow(unused_macros)]
 macro_rules! print_value {
     ($x:expr) => (println!("{} = {:?}", stringify!($x), $x))
 }
@@ -6,4 +7,25 @@ pub const EPSILON: f64 = 0.00001;
 
 pub fn almost_eq(a: f64, b: f64) -> bool {
     (a - b).abs() <= EPSILON
-}
\ No newline at end of file
+}
+
+/// Real roots of a*t^2 + b*t + c = 0, smallest first (twice the same root if a is 0)
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        return Some((-c / b, -c / b));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    // Avoids subtracting nearly equal numbers (cancellation) when b*b is much larger than 4ac
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+    let (t1, t2) = (q / a, c / q);
+    Some((t1.min(t2), t1.max(t2)))
+}
@@ -1,4 +1,4 @@
-use std::ops::{Add, Sub, Mul, Div, AddAssign, SubAssign, MulAssign, DivAssign, Neg, Rem, BitXor};
+use std::ops::{Add, Sub, Mul, Div, AddAssign, SubAssign, MulAssign, DivAssign, Neg, Rem, BitXor, Index};
 
 #[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct Vector {
@@ -17,7 +17,7 @@ pub struct Vector {
 
 impl Vector {
     pub fn new(x: f64, y: f64, z: f64) -> Vector {
-        Vector { x: x, y: y, z: z }
+        Vector { x, y, z }
     }
 
     pub fn dot(a: &Vector, b: &Vector) -> f64 {
@@ -74,6 +74,30 @@ impl Vector {
         let d = *other % *self;
         ((2.0 * d) * *other) - *self
     }
+
+    /// Refracts this direction through a surface with the given normal (facing against this direction),
+    /// where `eta` is the ratio of refractive indices (from / to). Returns None on total internal reflection.
+    pub fn refract_through(&self, normal: &Vector, eta: f64) -> Option<Vector> {
+        let cos_incident = -(*normal % *self);
+        let sin_transmitted_square = eta * eta * (1.0 - cos_incident * cos_incident);
+        if sin_transmitted_square > 1.0 {
+            return None;
+        }
+        let cos_transmitted = (1.0 - sin_transmitted_square).sqrt();
+        Some(*self * eta + *normal * (eta * cos_incident - cos_transmitted))
+    }
+
+    pub fn min(a: &Vector, b: &Vector) -> Vector {
+        Vector { x: a.x.min(b.x), y: a.y.min(b.y), z: a.z.min(b.z) }
+    }
+
+    pub fn max(a: &Vector, b: &Vector) -> Vector {
+        Vector { x: a.x.max(b.x), y: a.y.max(b.y), z: a.z.max(b.z) }
+    }
+
+    pub fn recip(&self) -> Vector {
+        Vector { x: 1.0 / self.x, y: 1.0 / self.y, z: 1.0 / self.z }
+    }
 }
 
 //
@@ -131,6 +155,18 @@ impl Div for Vector {
     }
 }
 
+impl Index<usize> for Vector {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector axis out of range: {}", axis),
+        }
+    }
+}
+
 impl Neg for Vector {
     type Output = Vector;
     fn neg(self) -> Vector {